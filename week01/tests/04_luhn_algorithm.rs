@@ -23,10 +23,42 @@ fn luhn_algorithm(mut payload: u64) -> bool {
     (10 - (sum % 10)) == check_digit
 }
 
+#[derive(Debug, Eq, PartialEq)]
+enum LuhnError {
+    EmptyInput,
+    UnknownCharacter(char),
+}
+
+/// Luhn mod N algorithm (https://en.wikipedia.org/wiki/Luhn_mod_N_algorithm).
+/// The modulus N is the size of the `alphabet`, and each character stands for its index within the
+/// alphabet. With `alphabet = ['0'..='9']`, it is the usual (base 10) Luhn algorithm.
+fn luhn_mod_n(input: &str, alphabet: &[char]) -> Result<bool, LuhnError> {
+    if input.is_empty() {
+        return Err(LuhnError::EmptyInput);
+    }
+
+    let n = alphabet.len();
+    let mut sum = 0;
+    // The rightmost character is the check character, which is not doubled
+    let mut double = false;
+    for character in input.chars().rev() {
+        let Some(mut code_point) = alphabet.iter().position(|c| *c == character) else {
+            return Err(LuhnError::UnknownCharacter(character));
+        };
+        if double {
+            code_point *= 2;
+        }
+        sum += code_point / n + code_point % n;
+        double = !double;
+    }
+
+    Ok(sum % n == 0)
+}
+
 /// Below you can find a set of unit tests.
 #[cfg(test)]
 mod tests {
-    use super::luhn_algorithm;
+    use super::{luhn_algorithm, luhn_mod_n, LuhnError};
 
     #[test]
     fn luhn_zero() {
@@ -57,4 +89,44 @@ mod tests {
         assert!(!luhn_algorithm(17893729977));
         assert!(!luhn_algorithm(123456));
     }
+
+    #[test]
+    fn luhn_mod_n_decimal() {
+        let digits: Vec<char> = ('0'..='9').collect();
+        for number in [
+            17893729974u64,
+            79927398713,
+            17893729975,
+            17893729976,
+            123456,
+        ] {
+            assert_eq!(
+                luhn_mod_n(&number.to_string(), &digits),
+                Ok(luhn_algorithm(number))
+            );
+        }
+    }
+
+    #[test]
+    fn luhn_mod_n_custom_alphabet() {
+        let alphabet = ['a', 'b', 'c', 'd', 'e', 'f'];
+        assert_eq!(luhn_mod_n("abcdb", &alphabet), Ok(true));
+        assert_eq!(luhn_mod_n("fedcbac", &alphabet), Ok(true));
+        assert_eq!(luhn_mod_n("abcda", &alphabet), Ok(false));
+        assert_eq!(luhn_mod_n("fedcbad", &alphabet), Ok(false));
+
+        let hex: Vec<char> = ('0'..='9').chain('a'..='f').collect();
+        assert_eq!(luhn_mod_n("1f39", &hex), Ok(true));
+        assert_eq!(luhn_mod_n("1f3a", &hex), Ok(false));
+    }
+
+    #[test]
+    fn luhn_mod_n_invalid_input() {
+        let alphabet = ['a', 'b', 'c'];
+        assert_eq!(
+            luhn_mod_n("abx", &alphabet),
+            Err(LuhnError::UnknownCharacter('x'))
+        );
+        assert_eq!(luhn_mod_n("", &alphabet), Err(LuhnError::EmptyInput));
+    }
 }
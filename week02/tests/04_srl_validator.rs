@@ -59,6 +59,27 @@ pub mod srl {
             Ok(SRL { protocol, address })
         }
 
+        /// Case-insensitive variant of [`SRL::new`].
+        /// ASCII letters are lowercased before validation, so the stored parts are always
+        /// lowercase. Other invalid characters are still rejected.
+        pub fn new_ci(srl: &str) -> Result<SRL, SRLValidationError> {
+            SRL::new(&srl.to_ascii_lowercase())
+        }
+
+        /// Strict variant of [`SRL::new`] that requires a protocol.
+        /// Returns [`SRLValidationError::EmptyProtocol`] if the SRL does not contain `://`.
+        pub fn new_require_protocol(srl: &str) -> Result<SRL, SRLValidationError> {
+            if !srl.contains("://") {
+                return Err(SRLValidationError::EmptyProtocol);
+            }
+            SRL::new(srl)
+        }
+
+        /// Returns true if the SRL contains a protocol.
+        pub fn is_absolute(&self) -> bool {
+            self.protocol.is_some()
+        }
+
         pub fn get_protocol(&self) -> Option<&str> {
             self.protocol.as_deref()
         }
@@ -166,4 +187,58 @@ mod tests {
         assert_eq!(srl.get_protocol(), Some("bar"));
         assert_eq!(srl.get_address(), "foobar");
     }
+
+    #[test]
+    fn case_insensitive() {
+        let srl = SRL::new_ci("HTTP://FOO").unwrap();
+        assert_eq!(srl.get_protocol(), Some("http"));
+        assert_eq!(srl.get_address(), "foo");
+
+        let srl = SRL::new_ci("fooBAR").unwrap();
+        assert_eq!(srl.get_protocol(), None);
+        assert_eq!(srl.get_address(), "foobar");
+    }
+
+    #[test]
+    fn case_insensitive_invalid() {
+        assert_eq!(
+            SRL::new_ci("HTTP://FO1O"),
+            Err(SRLValidationError::InvalidCharacterInAddress('1'))
+        );
+        assert_eq!(
+            SRL::new_ci("H_P://FOO"),
+            Err(SRLValidationError::InvalidCharacterInProtocol('_'))
+        );
+        assert_eq!(SRL::new_ci("://FOO"), Err(SRLValidationError::EmptyProtocol));
+    }
+
+    #[test]
+    fn require_protocol() {
+        let srl = SRL::new_require_protocol("bar://foobar").unwrap();
+        assert!(srl.is_absolute());
+        assert_eq!(srl.get_protocol(), Some("bar"));
+        assert_eq!(srl.get_address(), "foobar");
+
+        assert!(!SRL::new("foobar").unwrap().is_absolute());
+    }
+
+    #[test]
+    fn require_protocol_missing() {
+        assert_eq!(
+            SRL::new_require_protocol("foobar"),
+            Err(SRLValidationError::EmptyProtocol)
+        );
+        assert_eq!(
+            SRL::new_require_protocol(""),
+            Err(SRLValidationError::EmptyProtocol)
+        );
+        assert_eq!(
+            SRL::new_require_protocol("://foo"),
+            Err(SRLValidationError::EmptyProtocol)
+        );
+        assert_eq!(
+            SRL::new_require_protocol("bar://"),
+            Err(SRLValidationError::EmptyAddress)
+        );
+    }
 }
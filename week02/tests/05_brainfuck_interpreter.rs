@@ -13,37 +13,227 @@
 // Hint: Put `#[derive(Debug, Eq, PartialEq)]` on top of `ParseError`, `ExecuteError` and `Program`
 // (and any other custom types nested inside them) so that asserts in tests work.
 
+/// Maximum number of executed instructions, a program that executes more of them is considered
+/// to be an infinite loop.
+const MAX_INSTRUCTIONS: usize = 10000;
+
+/// Location of an instruction within the program source.
+/// `line` and `column` are 1-based, `index` is the 0-based character offset.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub index: usize,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum ParseError {
     UnknownInstruction { location: usize, instruction: char },
-    UnmatchedLoop { location: usize },
+    UnmatchedLoop { location: Position },
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum ExecuteError {
     NoInputLeft,
     InfiniteLoop,
+    /// The program accesses the data cells, but no data cells were provided
+    NoMemory,
+}
+
+/// Syntax accepted by [`parse_program_with`].
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Syntax {
+    /// The eight standard instructions only
+    Standard,
+    /// The standard instructions and two extensions:
+    /// - `#` records a snapshot of the data cells, see [`Program::execute_debug`]
+    /// - `!` ends the program, the rest of the source is used as its input
+    Extended,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Program {
     code: String,
     loops: Vec<(usize, usize)>,
+    /// Input embedded in the source after `!` (extended syntax only)
+    inline_input: Vec<u8>,
+}
+
+/// Instruction of a [`CompiledProgram`].
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Op {
+    /// Adds the (wrapping) value to the current cell
+    Add(i8),
+    /// Moves the data pointer by the given offset, stopping at the first or the last cell
+    Move(isize),
+    Output,
+    Input,
+    /// Jumps to the given op if the current cell is zero
+    JumpIfZero(usize),
+    /// Jumps to the given op if the current cell is not zero
+    JumpIfNonZero(usize),
+    /// Sets the current cell to zero, compiled from loops like `[-]` and `[+]`
+    Clear,
+}
+
+/// Program compiled to a sequence of [`Op`]s by [`Program::compile`].
+/// Runs of `+`/`-` and of `>`/`<` (in the same direction) are merged into single ops, loops that
+/// only add an odd value to the current cell (e.g. `[-]`) become [`Op::Clear`], and `#` snapshots
+/// are dropped.
+#[derive(Debug, Eq, PartialEq)]
+pub struct CompiledProgram {
+    ops: Vec<Op>,
+    inline_input: Vec<u8>,
+}
+
+impl CompiledProgram {
+    /// Executes the compiled program, with the same results as [`Program::execute`].
+    /// The instruction limit applies to the executed ops, so a compiled program can execute more
+    /// source instructions before it is considered to be an infinite loop.
+    pub fn execute(&self, input: Vec<u8>, data: Vec<u8>) -> Result<String, ExecuteError> {
+        self.execute_counted(input, data).map(|(output, _)| output)
+    }
+
+    /// Executes the compiled program like [`CompiledProgram::execute`], returning also the number
+    /// of executed ops.
+    pub fn execute_counted(
+        &self,
+        input: Vec<u8>,
+        mut data: Vec<u8>,
+    ) -> Result<(String, usize), ExecuteError> {
+        if !self.ops.is_empty() && data.is_empty() {
+            return Err(ExecuteError::NoMemory);
+        }
+        let input = [self.inline_input.as_slice(), &input].concat();
+        let mut output = String::new();
+        let mut index: usize = 0;
+        let mut data_ptr: usize = 0;
+        let mut input_ptr: usize = 0;
+        let mut instruction_counter: usize = 0;
+        while let Some(&op) = self.ops.get(index) {
+            if instruction_counter > MAX_INSTRUCTIONS {
+                return Err(ExecuteError::InfiniteLoop);
+            }
+            instruction_counter += 1;
+            match op {
+                Op::Add(value) => {
+                    data[data_ptr] = data[data_ptr].wrapping_add_signed(value);
+                }
+                Op::Move(offset) if offset >= 0 => {
+                    data_ptr = (data_ptr + offset as usize).min(data.len() - 1);
+                }
+                Op::Move(offset) => {
+                    data_ptr = data_ptr.saturating_sub(offset.unsigned_abs());
+                }
+                Op::Output => output.push(char::from(data[data_ptr])),
+                Op::Input => {
+                    let byte = input.get(input_ptr).ok_or(ExecuteError::NoInputLeft)?;
+                    data[data_ptr] = data[data_ptr].wrapping_add(*byte);
+                    input_ptr += 1;
+                }
+                Op::JumpIfZero(target) if data[data_ptr] == 0 => {
+                    index = target;
+                    continue;
+                }
+                Op::JumpIfNonZero(target) if data[data_ptr] != 0 => {
+                    index = target;
+                    continue;
+                }
+                Op::JumpIfZero(_) | Op::JumpIfNonZero(_) => {}
+                Op::Clear => data[data_ptr] = 0,
+            }
+            index += 1;
+        }
+
+        Ok((output, instruction_counter))
+    }
 }
 
 impl Program {
-    pub fn execute(&self, input: Vec<u8>, mut data: Vec<u8>) -> Result<String, ExecuteError> {
+    /// Compiles the program to bytecode, so that it can be executed repeatedly without
+    /// re-reading the source.
+    /// Loop jumps point right after the matching loop op.
+    pub fn compile(&self) -> CompiledProgram {
+        let mut ops = Vec::new();
+        let mut loop_starts = Vec::new();
+        for instruction in self.code.chars() {
+            let op = match instruction {
+                '+' => Op::Add(1),
+                '-' => Op::Add(-1),
+                '>' => Op::Move(1),
+                '<' => Op::Move(-1),
+                '.' => Op::Output,
+                ',' => Op::Input,
+                '[' => {
+                    loop_starts.push(ops.len());
+                    // The target is filled in once the loop end is found
+                    Op::JumpIfZero(0)
+                }
+                ']' => {
+                    // The program was parsed, so its loops are matched
+                    let start = loop_starts.pop().expect("unmatched loop");
+                    // Adding an odd value always reaches zero (within 256 iterations)
+                    if matches!(ops[start..], [Op::JumpIfZero(_), Op::Add(value)] if value % 2 != 0)
+                    {
+                        ops.truncate(start);
+                        ops.push(Op::Clear);
+                        continue;
+                    }
+                    ops[start] = Op::JumpIfZero(ops.len() + 1);
+                    Op::JumpIfNonZero(start + 1)
+                }
+                _ => continue,
+            };
+            match (ops.last_mut(), op) {
+                (Some(Op::Add(last)), Op::Add(value)) => *last = last.wrapping_add(value),
+                // Moves in opposite directions cannot be merged, because of the stops at the bounds
+                (Some(Op::Move(last)), Op::Move(offset)) if last.signum() == offset.signum() => {
+                    *last += offset
+                }
+                _ => ops.push(op),
+            }
+        }
+
+        CompiledProgram {
+            ops,
+            inline_input: self.inline_input.clone(),
+        }
+    }
+
+    /// Executes the program with the given `input` and initial `data` cells.
+    /// Cell arithmetic wraps around and the data pointer never moves outside of `data`, so the
+    /// execution never panics.
+    pub fn execute(&self, input: Vec<u8>, data: Vec<u8>) -> Result<String, ExecuteError> {
+        self.execute_debug(input, data, &mut vec![])
+    }
+
+    /// Same as [`Program::execute`], but every executed `#` instruction pushes a copy of the data
+    /// cells to `snapshots`.
+    /// The inline input of the program (if any) is read before `input`.
+    pub fn execute_debug(
+        &self,
+        input: Vec<u8>,
+        mut data: Vec<u8>,
+        snapshots: &mut Vec<Vec<u8>>,
+    ) -> Result<String, ExecuteError> {
+        let input = [self.inline_input.as_slice(), &input].concat();
+        // Loop positions are character indices, so the program cannot be indexed by bytes
+        let code: Vec<char> = self.code.chars().collect();
         let mut output = String::new();
         let mut index: usize = 0;
         let mut data_ptr: usize = 0;
         let mut input_ptr: usize = 0;
         let mut instruction_counter: usize = 0;
-        while index < self.code.len() {
-            if instruction_counter > 10000 {
+        while let Some(&instruction) = code.get(index) {
+            if instruction_counter > MAX_INSTRUCTIONS {
                 return Err(ExecuteError::InfiniteLoop);
             }
-            let instruction = *self.code.as_bytes().get(index).unwrap() as char;
             instruction_counter += 1;
+            if matches!(instruction, '>' | '<' | '[' | ']' | '+' | '-' | '.' | ',')
+                && data.is_empty()
+            {
+                return Err(ExecuteError::NoMemory);
+            }
             match instruction {
                 '>' if data_ptr + 1 < data.len() => {
                     data_ptr += 1;
@@ -52,30 +242,33 @@ impl Program {
                     data_ptr -= 1;
                 }
                 '+' => {
-                    data[data_ptr] += 1;
+                    data[data_ptr] = data[data_ptr].wrapping_add(1);
                 }
                 '-' => {
-                    data[data_ptr] -= 1;
+                    data[data_ptr] = data[data_ptr].wrapping_sub(1);
                 }
                 '.' => output.push(char::from(data[data_ptr])),
                 ',' => {
                     if let Some(byte) = input.get(input_ptr) {
-                        data[data_ptr] += byte;
+                        data[data_ptr] = data[data_ptr].wrapping_add(*byte);
                         input_ptr += 1;
                     } else {
                         return Err(ExecuteError::NoInputLeft);
                     }
                 }
                 '[' if data[data_ptr] == 0 => {
-                    index = self.loops[index].1 + 1;
-                    continue;
+                    if let Some((_, end)) = self.loops.iter().find(|(start, _)| *start == index) {
+                        index = end + 1;
+                        continue;
+                    }
                 }
                 ']' if data[data_ptr] != 0 => {
-                    if let Some((position, _)) = self.loops.iter().find(|(_, end)| *end == index) {
-                        index = position + 1;
+                    if let Some((start, _)) = self.loops.iter().find(|(_, end)| *end == index) {
+                        index = start + 1;
                         continue;
                     }
                 }
+                '#' => snapshots.push(data.clone()),
                 _ => {}
             }
             index += 1;
@@ -83,21 +276,77 @@ impl Program {
 
         Ok(output)
     }
+
+    /// Renders the program as an indented tree, one loop bracket per line.
+    /// Runs of other instructions are kept together on a single line and every loop body is
+    /// indented by two more spaces than its brackets.
+    pub fn to_ast_string(&self) -> String {
+        fn flush(ast: &mut String, run: &mut String, depth: usize) {
+            if !run.is_empty() {
+                ast.push_str(&format!("{}{run}\n", "  ".repeat(depth)));
+                run.clear();
+            }
+        }
+
+        let mut ast = String::new();
+        let mut run = String::new();
+        let mut depth = 0;
+        for (index, instruction) in self.code.chars().enumerate() {
+            if self.loops.iter().any(|(start, _)| *start == index) {
+                flush(&mut ast, &mut run, depth);
+                ast.push_str(&format!("{}[\n", "  ".repeat(depth)));
+                depth += 1;
+            } else if self.loops.iter().any(|(_, end)| *end == index) {
+                flush(&mut ast, &mut run, depth);
+                depth -= 1;
+                ast.push_str(&format!("{}]\n", "  ".repeat(depth)));
+            } else if !instruction.is_whitespace() {
+                run.push(instruction);
+            }
+        }
+        flush(&mut ast, &mut run, depth);
+        ast
+    }
 }
 
 pub fn parse_program(program: &str) -> Result<Program, ParseError> {
-    let mut stack = Vec::<usize>::new();
+    parse_program_with(program, Syntax::Standard)
+}
+
+/// Parses the `program` with the given `syntax`.
+/// In the [`Syntax::Standard`] syntax, `#` and `!` are unknown instructions.
+pub fn parse_program_with(program: &str, syntax: Syntax) -> Result<Program, ParseError> {
+    let (program, inline_input) = match syntax {
+        Syntax::Extended => program.split_once('!').unwrap_or((program, "")),
+        Syntax::Standard => (program, ""),
+    };
+    let mut stack = Vec::<Position>::new();
     let mut loops = Vec::<(usize, usize)>::new();
+    let mut line = 1;
+    let mut column = 0;
 
     for (location, instruction) in program.chars().enumerate() {
+        column += 1;
+        let position = Position {
+            line,
+            column,
+            index: location,
+        };
         match instruction {
             '>' | '<' | '+' | '-' | '.' | ',' => continue,
-            '[' => stack.push(location),
+            '#' if syntax == Syntax::Extended => continue,
+            '\n' => {
+                line += 1;
+                column = 0;
+            }
+            // Whitespace is ignored, so that programs can be split across multiple lines
+            instruction if instruction.is_whitespace() => continue,
+            '[' => stack.push(position),
             ']' => {
                 if let Some(start) = stack.pop() {
-                    loops.push((start, location))
+                    loops.push((start.index, location))
                 } else {
-                    return Err(ParseError::UnmatchedLoop { location });
+                    return Err(ParseError::UnmatchedLoop { location: position });
                 }
             }
             _ => {
@@ -109,22 +358,25 @@ pub fn parse_program(program: &str) -> Result<Program, ParseError> {
         }
     }
 
-    if stack.is_empty() {
-        Ok(Program {
+    // Report the outermost unmatched loop start
+    match stack.first().copied() {
+        None => Ok(Program {
             code: program.to_string(),
             loops,
-        })
-    } else {
-        Err(ParseError::UnmatchedLoop {
-            location: stack.pop().unwrap_or_default(),
-        })
+            inline_input: inline_input.as_bytes().to_vec(),
+        }),
+        Some(location) => Err(ParseError::UnmatchedLoop { location }),
     }
 }
 
 /// Below you can find a set of unit tests.
 #[cfg(test)]
 mod tests {
-    use crate::{parse_program, ExecuteError, ParseError};
+    use crate::{
+        parse_program, parse_program_with, CompiledProgram, ExecuteError, Op, ParseError, Position,
+        Syntax,
+    };
+    use proptest::prelude::*;
 
     #[test]
     fn parse_empty() {
@@ -146,7 +398,37 @@ mod tests {
     fn parse_unmatched_loop_start() {
         assert_eq!(
             parse_program(">++[+>][++>"),
-            Err(ParseError::UnmatchedLoop { location: 7 })
+            Err(ParseError::UnmatchedLoop {
+                location: Position {
+                    line: 1,
+                    column: 8,
+                    index: 7
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn parse_unmatched_loop_outermost() {
+        assert_eq!(
+            parse_program("[[["),
+            Err(ParseError::UnmatchedLoop {
+                location: Position {
+                    line: 1,
+                    column: 1,
+                    index: 0
+                }
+            })
+        );
+        assert_eq!(
+            parse_program("+[]+[[-]\n[[]"),
+            Err(ParseError::UnmatchedLoop {
+                location: Position {
+                    line: 1,
+                    column: 5,
+                    index: 4
+                }
+            })
         );
     }
 
@@ -154,10 +436,45 @@ mod tests {
     fn parse_unmatched_loop_end() {
         assert_eq!(
             parse_program(">++[+>][++>]+]"),
-            Err(ParseError::UnmatchedLoop { location: 13 })
+            Err(ParseError::UnmatchedLoop {
+                location: Position {
+                    line: 1,
+                    column: 14,
+                    index: 13
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn parse_unmatched_loop_multiline() {
+        assert_eq!(
+            parse_program("++[>+<-]\n>>[\n  +++]]\n."),
+            Err(ParseError::UnmatchedLoop {
+                location: Position {
+                    line: 3,
+                    column: 7,
+                    index: 19
+                }
+            })
+        );
+        assert_eq!(
+            parse_program("+\n[>+\n<-"),
+            Err(ParseError::UnmatchedLoop {
+                location: Position {
+                    line: 2,
+                    column: 1,
+                    index: 2
+                }
+            })
         );
     }
 
+    #[test]
+    fn multiline_program() {
+        check_output("+++++++++++++++++++++++++++++++++\n.\n  .", "", "!!");
+    }
+
     #[test]
     fn missing_input() {
         let program = parse_program(",").unwrap();
@@ -192,6 +509,256 @@ mod tests {
         check_output("++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.", "", "Hello World!\n");
     }
 
+    #[test]
+    fn compiled_hello_world() {
+        let program = parse_program("++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.").unwrap();
+        let compiled = program.compile();
+        let expected = Ok("Hello World!\n".to_string());
+        assert_eq!(program.execute(vec![], vec![0; 30000]), expected);
+        assert_eq!(compiled.execute(vec![], vec![0; 30000]), expected);
+        // The compiled program can be executed repeatedly
+        assert_eq!(compiled.execute(vec![], vec![0; 30000]), expected);
+    }
+
+    #[test]
+    fn compiled_ops() {
+        let program = parse_program("+++ --[->>+<<]\n,.").unwrap();
+        assert_eq!(
+            program.compile().ops,
+            vec![
+                Op::Add(1),
+                Op::JumpIfZero(7),
+                Op::Add(-1),
+                Op::Move(2),
+                Op::Add(1),
+                Op::Move(-2),
+                Op::JumpIfNonZero(2),
+                Op::Input,
+                Op::Output,
+            ]
+        );
+        // Opposite moves are kept apart, wrapping additions are merged
+        let program = parse_program(&format!("><{}", "+".repeat(300))).unwrap();
+        assert_eq!(
+            program.compile().ops,
+            vec![Op::Move(1), Op::Move(-1), Op::Add(44)]
+        );
+    }
+
+    #[test]
+    fn compiled_clear_loop() {
+        let program = parse_program("+++[-]++[+].>+++++[---].[--]").unwrap();
+        let compiled = program.compile();
+        assert_eq!(
+            compiled.ops,
+            vec![
+                Op::Add(3),
+                Op::Clear,
+                Op::Add(2),
+                Op::Clear,
+                Op::Output,
+                Op::Move(1),
+                Op::Add(5),
+                Op::Clear,
+                Op::Output,
+                // Adding an even value does not have to reach zero
+                Op::JumpIfZero(12),
+                Op::Add(-2),
+                Op::JumpIfNonZero(10),
+            ]
+        );
+        let expected = program.execute(vec![], vec![0; 2]);
+        assert_eq!(expected, Ok("\0\0".to_string()));
+        assert_eq!(compiled.execute(vec![], vec![0; 2]), expected);
+
+        let program = parse_program("++++++++++[-].").unwrap();
+        let looping = CompiledProgram {
+            ops: vec![
+                Op::Add(10),
+                Op::JumpIfZero(4),
+                Op::Add(-1),
+                Op::JumpIfNonZero(2),
+                Op::Output,
+            ],
+            inline_input: vec![],
+        };
+        let (output, looping_steps) = looping.execute_counted(vec![], vec![0; 1]).unwrap();
+        let (clear_output, clear_steps) = program
+            .compile()
+            .execute_counted(vec![], vec![0; 1])
+            .unwrap();
+        assert_eq!(clear_output, output);
+        assert_eq!(clear_steps, 3);
+        assert!(clear_steps < looping_steps);
+    }
+
+    #[test]
+    fn compiled_edge_cases() {
+        for (code, data_len) in [
+            ("", 0),
+            ("+", 0),
+            ("<<<<<.", 3),
+            (">>>>>+<.>.", 3),
+            ("-.", 1),
+            (",.,.", 1),
+            ("+[]", 1),
+            ("+\u{3000}[-]+.", 1),
+        ] {
+            let program = parse_program(code).unwrap();
+            assert_eq!(
+                program.compile().execute(b"a".to_vec(), vec![0; data_len]),
+                program.execute(b"a".to_vec(), vec![0; data_len]),
+                "{code}"
+            );
+        }
+
+        let program = parse_program_with(",.>,.!ab#", Syntax::Extended).unwrap();
+        assert_eq!(
+            program.compile().execute(vec![], vec![0; 2]),
+            Ok("ab".to_string())
+        );
+    }
+
+    #[test]
+    fn ast_nested_loops() {
+        let program = parse_program("++[>+\n[-]<-]>.").unwrap();
+        assert_eq!(
+            program.to_ast_string(),
+            "++
+[
+  >+
+  [
+    -
+  ]
+  <-
+]
+>.
+"
+        );
+    }
+
+    #[test]
+    fn ast_without_loops() {
+        assert_eq!(parse_program("").unwrap().to_ast_string(), "");
+        assert_eq!(parse_program("+>.").unwrap().to_ast_string(), "+>.\n");
+    }
+
+    #[test]
+    fn execute_edge_cases() {
+        assert_eq!(
+            parse_program("").unwrap().execute(vec![], vec![]),
+            Ok(String::new())
+        );
+        assert_eq!(
+            parse_program("+").unwrap().execute(vec![], vec![]),
+            Err(ExecuteError::NoMemory)
+        );
+        check_output("<<<<<.", "", "\0");
+        check_output("-.", "", "\u{ff}");
+        check_output(">>>>>.", "", "\0");
+        // Multi-byte whitespace does not shift the loop positions
+        check_output("+\u{3000}[-]+.", "", "\u{1}");
+    }
+
+    #[test]
+    fn extended_snapshots() {
+        let program = parse_program_with("++#>+++#<[->+<]#", Syntax::Extended).unwrap();
+        let mut snapshots = vec![];
+        let output = program.execute_debug(vec![], vec![0; 3], &mut snapshots);
+        assert_eq!(output, Ok(String::new()));
+        assert_eq!(snapshots, vec![vec![2, 0, 0], vec![2, 3, 0], vec![0, 5, 0]]);
+
+        // `#` inside a loop records a snapshot in every iteration
+        let program = parse_program_with("+++[#-]", Syntax::Extended).unwrap();
+        let mut snapshots = vec![];
+        program
+            .execute_debug(vec![], vec![0; 1], &mut snapshots)
+            .unwrap();
+        assert_eq!(snapshots, vec![vec![3], vec![2], vec![1]]);
+    }
+
+    #[test]
+    fn extended_inline_input() {
+        let program = parse_program_with(",.>,.>,.!hi!", Syntax::Extended).unwrap();
+        assert_eq!(program.execute(vec![], vec![0; 3]), Ok("hi!".to_string()));
+
+        // The inline input is read before the regular input
+        let program = parse_program_with(",.>,.!a", Syntax::Extended).unwrap();
+        assert_eq!(
+            program.execute(b"b".to_vec(), vec![0; 2]),
+            Ok("ab".to_string())
+        );
+
+        // Without `!`, the whole source is the program
+        let program = parse_program_with(",.", Syntax::Extended).unwrap();
+        assert_eq!(
+            program.execute(vec![], vec![0; 1]),
+            Err(ExecuteError::NoInputLeft)
+        );
+    }
+
+    #[test]
+    fn standard_rejects_extensions() {
+        assert_eq!(
+            parse_program("+#"),
+            Err(ParseError::UnknownInstruction {
+                location: 1,
+                instruction: '#'
+            })
+        );
+        assert_eq!(
+            parse_program_with(",!x", Syntax::Standard),
+            Err(ParseError::UnknownInstruction {
+                location: 1,
+                instruction: '!'
+            })
+        );
+        // The inline input is not parsed as instructions
+        assert!(parse_program_with("+!#[", Syntax::Extended).is_ok());
+    }
+
+    proptest! {
+        #[test]
+        fn execute_never_panics(
+            code in "[<>+\\-.,\\[\\] \n]{0,64}",
+            input in proptest::collection::vec(any::<u8>(), 0..16),
+            data_len in 0usize..32,
+        ) {
+            let program = parse_program(&balance_loops(&code)).unwrap();
+            let _ = program.execute(input, vec![0; data_len]);
+        }
+
+        #[test]
+        fn compiled_matches_interpreted(
+            code in "[<>+\\-.,\\[\\] \n]{0,64}",
+            input in proptest::collection::vec(any::<u8>(), 0..16),
+            data_len in 1usize..32,
+        ) {
+            let program = parse_program(&balance_loops(&code)).unwrap();
+            // The compiled program executes fewer ops, so it can only run into the limit later
+            if let Ok(output) = program.execute(input.clone(), vec![0; data_len]) {
+                prop_assert_eq!(program.compile().execute(input, vec![0; data_len]), Ok(output));
+            }
+        }
+    }
+
+    /// Drops unmatched loop ends and closes unmatched loop starts of `code`
+    fn balance_loops(code: &str) -> String {
+        let mut depth = 0;
+        let mut balanced = String::new();
+        for instruction in code.chars() {
+            match instruction {
+                '[' => depth += 1,
+                ']' if depth == 0 => continue,
+                ']' => depth -= 1,
+                _ => {}
+            }
+            balanced.push(instruction);
+        }
+        balanced.push_str(&"]".repeat(depth));
+        balanced
+    }
+
     fn check_output(program_text: &str, input: &str, expected_output: &str) {
         let program = parse_program(program_text);
         match program {
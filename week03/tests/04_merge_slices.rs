@@ -6,22 +6,54 @@
 // Bonus: Can you build a complete merge sort on top of this function? :)
 
 // Used pseudocode from: https://en.wikipedia.org/wiki/Merge_sort#Top-down_implementation_using_lists
-fn merge_slices(mut left: &[u32], mut right: &[u32]) -> Vec<u32> {
-    let mut result = Vec::<u32>::with_capacity(left.len() + right.len());
-    loop {
-        match (left.first(), right.first()) {
-            (Some(l), r) if r.is_none() || l <= r.unwrap() => {
-                result.push(*l);
-                left = &left[1..]
+fn merge_slices(left: &[u32], right: &[u32]) -> Vec<u32> {
+    merge_iter(left, right).cloned().collect()
+}
+
+/// Lazily merges two sorted slices, yielding references to their items in sorted order.
+/// No comparisons are made until the iterator is polled.
+fn merge_iter<'a, T: Ord>(left: &'a [T], right: &'a [T]) -> impl Iterator<Item = &'a T> {
+    MergeIter {
+        left,
+        right,
+        left_index: 0,
+        right_index: 0,
+    }
+}
+
+struct MergeIter<'a, T> {
+    left: &'a [T],
+    right: &'a [T],
+    left_index: usize,
+    right_index: usize,
+}
+
+impl<'a, T: Ord> Iterator for MergeIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.left.get(self.left_index), self.right.get(self.right_index)) {
+            (Some(l), Some(r)) if l > r => {
+                self.right_index += 1;
+                Some(r)
             }
-            (l, Some(r)) if l.is_none() || l.unwrap() > r => {
-                result.push(*r);
-                right = &right[1..]
+            (Some(l), _) => {
+                self.left_index += 1;
+                Some(l)
             }
-            _ => break,
+            (None, Some(r)) => {
+                self.right_index += 1;
+                Some(r)
+            }
+            (None, None) => None,
         }
     }
-    result
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining =
+            (self.left.len() - self.left_index) + (self.right.len() - self.right_index);
+        (remaining, Some(remaining))
+    }
 }
 
 fn mergesort(items: &[u32]) -> Vec<u32> {
@@ -36,7 +68,9 @@ fn mergesort(items: &[u32]) -> Vec<u32> {
 /// Below you can find a set of unit tests.
 #[cfg(test)]
 mod tests {
-    use crate::{merge_slices, mergesort};
+    use crate::{merge_iter, merge_slices, mergesort};
+    use std::cell::Cell;
+    use std::cmp::Ordering;
 
     #[test]
     fn merge_slices_empty() {
@@ -76,6 +110,73 @@ mod tests {
         assert_eq!(merge_slices(&[1, 9, 11], &[]), vec![1, 9, 11]);
     }
 
+    #[test]
+    fn merge_iter_collect() {
+        assert_eq!(
+            merge_iter(&[1, 4, 6, 8], &[0, 1, 1, 3, 4, 5, 7, 8, 9])
+                .copied()
+                .collect::<Vec<u32>>(),
+            vec![0, 1, 1, 1, 3, 4, 4, 5, 6, 7, 8, 8, 9]
+        );
+        assert_eq!(merge_iter::<u32>(&[], &[]).next(), None);
+        assert_eq!(
+            merge_iter(&["a", "c"], &["b"]).collect::<Vec<_>>(),
+            vec![&"a", &"b", &"c"]
+        );
+    }
+
+    /// Counts how many times it was compared, to observe the laziness of `merge_iter`
+    struct Counted<'a> {
+        value: u32,
+        comparisons: &'a Cell<usize>,
+    }
+
+    impl PartialEq for Counted<'_> {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Counted<'_> {}
+
+    impl PartialOrd for Counted<'_> {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Counted<'_> {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.comparisons.set(self.comparisons.get() + 1);
+            self.value.cmp(&other.value)
+        }
+    }
+
+    #[test]
+    fn merge_iter_is_lazy() {
+        let comparisons = Cell::new(0);
+        let counted = |values: &[u32]| {
+            values
+                .iter()
+                .map(|&value| Counted {
+                    value,
+                    comparisons: &comparisons,
+                })
+                .collect::<Vec<_>>()
+        };
+        let left = counted(&[1, 3, 5]);
+        let right = counted(&[2, 4, 6]);
+
+        let mut iter = merge_iter(&left, &right);
+        assert_eq!(comparisons.get(), 0);
+
+        assert_eq!(iter.next().map(|item| item.value), Some(1));
+        assert_eq!(comparisons.get(), 1);
+
+        assert_eq!(iter.next().map(|item| item.value), Some(2));
+        assert_eq!(comparisons.get(), 2);
+    }
+
     // Mergesort tests
     #[test]
     fn mergesort_empty() {
@@ -19,17 +19,7 @@ where
     T: AsRef<str>,
 {
     fn eq(&self, other: &T) -> bool {
-        let mut it_a = self.0.chars();
-        let mut it_b = other.as_ref().chars();
-        loop {
-            let Some(a) = it_a.next() else { break };
-            let Some(b) = it_b.next() else { break };
-            if a.to_ascii_lowercase() != b.to_ascii_lowercase() {
-                return false;
-            }
-        }
-
-        self.0.len() == other.as_ref().len()
+        self.0.eq_ignore_ascii_case(other.as_ref())
     }
 }
 
@@ -44,20 +34,10 @@ where
             Ordering::Equal => (),
         }
 
-        let mut it_a = self.0.chars();
-        let mut it_b = other.as_ref().chars();
-
-        loop {
-            let Some(a) = it_a.next() else { break };
-            let Some(b) = it_b.next() else { break };
-            match a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase()) {
-                Ordering::Less => return Some(Ordering::Less),
-                Ordering::Greater => return Some(Ordering::Greater),
-                Ordering::Equal => (),
-            }
-        }
-
-        Some(Ordering::Equal)
+        // Folds the case byte by byte, like `eq_ignore_ascii_case`, so both comparisons agree
+        let a = self.0.bytes().map(|byte| byte.to_ascii_lowercase());
+        let b = other.as_ref().bytes().map(|byte| byte.to_ascii_lowercase());
+        Some(a.cmp(b))
     }
 }
 
@@ -10,12 +10,87 @@ trait Shape {
 
 struct Circle {
     r: f64,
+    center: (f64, f64),
 }
 
 impl Circle {
     fn new(r: f64) -> Self {
-        Circle { r }
+        Circle {
+            r,
+            center: (0.0, 0.0),
+        }
     }
+
+    fn with_center(center: (f64, f64), r: f64) -> Self {
+        Circle { r, center }
+    }
+
+    fn center(&self) -> (f64, f64) {
+        self.center
+    }
+
+    fn contains(&self, point: (f64, f64)) -> bool {
+        distance(self.center, point) <= self.r + 1e-9
+    }
+
+    /// Smallest circle that has `a` and `b` on its boundary
+    fn from_two(a: (f64, f64), b: (f64, f64)) -> Self {
+        let center = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        Circle::with_center(center, distance(a, b) / 2.0)
+    }
+
+    /// Circle passing through `a`, `b` and `c`.
+    /// For (nearly) collinear points, the circle spanned by the two farthest points is returned.
+    fn from_three(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> Self {
+        let (bx, by) = (b.0 - a.0, b.1 - a.1);
+        let (cx, cy) = (c.0 - a.0, c.1 - a.1);
+        let d = 2.0 * (bx * cy - by * cx);
+        if d.abs() < 1e-12 {
+            return [(a, b), (a, c), (b, c)]
+                .into_iter()
+                .map(|(p, q)| Circle::from_two(p, q))
+                .max_by(|x, y| x.r.total_cmp(&y.r))
+                .unwrap();
+        }
+
+        let b2 = bx * bx + by * by;
+        let c2 = cx * cx + cy * cy;
+        let ux = (cy * b2 - by * c2) / d;
+        let uy = (bx * c2 - cx * b2) / d;
+        Circle::with_center((a.0 + ux, a.1 + uy), ux.hypot(uy))
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Returns the smallest circle enclosing all `points` (incremental Welzl's algorithm).
+/// An empty set of points produces a circle with zero radius at the origin.
+fn bounding_circle(points: &[(f64, f64)]) -> Circle {
+    let Some(&first) = points.first() else {
+        return Circle::new(0.0);
+    };
+
+    let mut circle = Circle::with_center(first, 0.0);
+    for i in 1..points.len() {
+        if circle.contains(points[i]) {
+            continue;
+        }
+        circle = Circle::with_center(points[i], 0.0);
+        for j in 0..i {
+            if circle.contains(points[j]) {
+                continue;
+            }
+            circle = Circle::from_two(points[i], points[j]);
+            for k in 0..j {
+                if !circle.contains(points[k]) {
+                    circle = Circle::from_three(points[i], points[j], points[k]);
+                }
+            }
+        }
+    }
+    circle
 }
 
 impl Shape for Circle {
@@ -49,10 +124,232 @@ impl Shape for Rectangle {
     }
 }
 
+/// Ellipse with semi-axes `a` and `b`
+struct Ellipse {
+    a: f64,
+    b: f64,
+}
+
+impl Ellipse {
+    fn new(a: f64, b: f64) -> Self {
+        Ellipse { a, b }
+    }
+}
+
+impl Shape for Ellipse {
+    fn area(&self) -> f64 {
+        std::f64::consts::PI * self.a * self.b
+    }
+
+    /// Ramanujan's second approximation, exact for circles
+    fn perimeter(&self) -> f64 {
+        let sum = self.a + self.b;
+        if sum == 0.0 {
+            return 0.0;
+        }
+        let h = ((self.a - self.b) / sum).powi(2);
+        std::f64::consts::PI * sum * (1.0 + 3.0 * h / (10.0 + (4.0 - 3.0 * h).sqrt()))
+    }
+}
+
+/// Triangle given by the lengths of its sides
+struct Triangle {
+    a: f64,
+    b: f64,
+    c: f64,
+}
+
+impl Triangle {
+    fn new(a: f64, b: f64, c: f64) -> Self {
+        Triangle { a, b, c }
+    }
+}
+
+impl Shape for Triangle {
+    /// Heron's formula
+    fn area(&self) -> f64 {
+        let s = self.perimeter() / 2.0;
+        (s * (s - self.a) * (s - self.b) * (s - self.c))
+            .max(0.0)
+            .sqrt()
+    }
+
+    fn perimeter(&self) -> f64 {
+        self.a + self.b + self.c
+    }
+}
+
+/// Simple polygon given by its vertices in order (either clockwise or counter-clockwise)
+struct Polygon {
+    vertices: Vec<(f64, f64)>,
+}
+
+impl Polygon {
+    fn new(vertices: Vec<(f64, f64)>) -> Result<Self, String> {
+        if vertices.len() < 3 {
+            return Err(format!(
+                "Polygon needs at least 3 vertices, got {}",
+                vertices.len()
+            ));
+        }
+        Ok(Polygon { vertices })
+    }
+
+    /// Iterates over the edges of the polygon, including the one closing the loop
+    fn edges(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        self.vertices
+            .iter()
+            .copied()
+            .zip(self.vertices.iter().copied().cycle().skip(1))
+    }
+}
+
+impl Shape for Polygon {
+    /// Shoelace formula
+    fn area(&self) -> f64 {
+        let sum: f64 = self.edges().map(|(a, b)| a.0 * b.1 - b.0 * a.1).sum();
+        sum.abs() / 2.0
+    }
+
+    fn perimeter(&self) -> f64 {
+        self.edges().map(|(a, b)| distance(a, b)).sum()
+    }
+}
+
+/// Circular sector (pie slice) with radius `r` and central angle `angle_rad` (in radians)
+struct Sector {
+    r: f64,
+    angle_rad: f64,
+}
+
+impl Sector {
+    fn new(r: f64, angle_rad: f64) -> Result<Self, String> {
+        if r < 0.0 {
+            return Err(format!("Sector radius cannot be negative, got {r}"));
+        }
+        if angle_rad < 0.0 {
+            return Err(format!("Sector angle cannot be negative, got {angle_rad}"));
+        }
+        Ok(Sector { r, angle_rad })
+    }
+}
+
+impl Shape for Sector {
+    fn area(&self) -> f64 {
+        0.5 * self.r.powi(2) * self.angle_rad
+    }
+
+    /// The arc and the two radii
+    fn perimeter(&self) -> f64 {
+        self.r * self.angle_rad + 2.0 * self.r
+    }
+}
+
+/// Comparison of geometrical objects that tolerates floating point errors
+trait ApproxEq {
+    /// Returns true if the defining parameters of `self` and `other` differ by at most `epsilon`.
+    fn approx_eq(&self, other: &Self, epsilon: f64) -> bool;
+}
+
+fn approx_eq(a: f64, b: f64, epsilon: f64) -> bool {
+    (a - b).abs() <= epsilon
+}
+
+impl ApproxEq for Circle {
+    fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        approx_eq(self.r, other.r, epsilon)
+            && approx_eq(self.center.0, other.center.0, epsilon)
+            && approx_eq(self.center.1, other.center.1, epsilon)
+    }
+}
+
+impl ApproxEq for Rectangle {
+    fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        approx_eq(self.a, other.a, epsilon) && approx_eq(self.b, other.b, epsilon)
+    }
+}
+
+/// Returns true if the areas of two (possibly different kinds of) shapes differ by at most `eps`.
+fn shapes_area_approx_eq(a: &dyn Shape, b: &dyn Shape, eps: f64) -> bool {
+    approx_eq(a.area(), b.area(), eps)
+}
+
+/// Returns the shape with the largest area, or the first one of them if there are more.
+/// Shapes whose area is NaN are skipped.
+fn largest_by_area(shapes: &[Box<dyn Shape>]) -> Option<&dyn Shape> {
+    shapes
+        .iter()
+        .map(|shape| shape.as_ref())
+        .filter(|shape| !shape.area().is_nan())
+        .reduce(|largest, shape| {
+            if shape.area() > largest.area() {
+                shape
+            } else {
+                largest
+            }
+        })
+}
+
+/// Three-dimensional geometrical object, independent of the two-dimensional [`Shape`]
+trait Solid {
+    fn volume(&self) -> f64;
+    fn surface_area(&self) -> f64;
+}
+
+struct Sphere {
+    r: f64,
+}
+
+impl Sphere {
+    fn new(r: f64) -> Self {
+        Sphere { r }
+    }
+}
+
+impl Solid for Sphere {
+    fn volume(&self) -> f64 {
+        4.0 / 3.0 * std::f64::consts::PI * self.r.powi(3)
+    }
+
+    fn surface_area(&self) -> f64 {
+        4.0 * std::f64::consts::PI * self.r.powi(2)
+    }
+}
+
+/// Rectangular cuboid with edges `a`, `b` and `c`
+struct Cuboid {
+    a: f64,
+    b: f64,
+    c: f64,
+}
+
+impl Cuboid {
+    fn new(a: f64, b: f64, c: f64) -> Self {
+        Cuboid { a, b, c }
+    }
+}
+
+impl Solid for Cuboid {
+    fn volume(&self) -> f64 {
+        self.a * self.b * self.c
+    }
+
+    fn surface_area(&self) -> f64 {
+        2.0 * (self.a * self.b + self.b * self.c + self.a * self.c)
+    }
+}
+
+fn total_volume(solids: &[Box<dyn Solid>]) -> f64 {
+    solids.iter().map(|solid| solid.volume()).sum()
+}
+
 /// Below you can find a set of unit tests.
 #[cfg(test)]
 mod tests {
-    use crate::{Circle, Rectangle, Shape};
+    use crate::{
+        bounding_circle, largest_by_area, shapes_area_approx_eq, total_volume, ApproxEq, Circle,
+        Cuboid, Ellipse, Polygon, Rectangle, Sector, Shape, Solid, Sphere, Triangle,
+    };
     use std::f64::consts::PI;
 
     #[test]
@@ -97,6 +394,222 @@ mod tests {
         assert_almost_eq(rectangle.perimeter(), 0.0);
     }
 
+    #[test]
+    fn ellipse_circle() {
+        for r in [0.0, 1.0, 5.0, 122038.12] {
+            let ellipse = Ellipse::new(r, r);
+            let circle = Circle::new(r);
+            assert_almost_eq(ellipse.area(), circle.area());
+            assert_almost_eq(ellipse.perimeter(), circle.perimeter());
+        }
+    }
+
+    #[test]
+    fn ellipse() {
+        let ellipse = Ellipse::new(5.0, 3.0);
+        assert_almost_eq(ellipse.area(), 15.0 * PI);
+        assert_almost_eq(ellipse.perimeter(), 25.527);
+
+        let ellipse = Ellipse::new(1.0, 10.0);
+        assert_almost_eq(ellipse.area(), 10.0 * PI);
+        assert_almost_eq(ellipse.perimeter(), 40.6397);
+    }
+
+    #[test]
+    fn polygon_unit_square() {
+        let polygon = Polygon::new(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]).unwrap();
+        assert_almost_eq(polygon.area(), 1.0);
+        assert_almost_eq(polygon.perimeter(), 4.0);
+
+        // Clockwise order and an offset do not matter
+        let polygon = Polygon::new(vec![(2.0, 1.0), (2.0, 4.0), (7.0, 4.0), (7.0, 1.0)]).unwrap();
+        let rectangle = Rectangle::new(5.0, 3.0);
+        assert_almost_eq(polygon.area(), rectangle.area());
+        assert_almost_eq(polygon.perimeter(), rectangle.perimeter());
+    }
+
+    #[test]
+    fn polygon_triangle() {
+        let polygon = Polygon::new(vec![(0.0, 0.0), (4.0, 0.0), (0.0, 3.0)]).unwrap();
+        let triangle = Triangle::new(3.0, 4.0, 5.0);
+        assert_almost_eq(polygon.area(), 6.0);
+        assert_almost_eq(polygon.area(), triangle.area());
+        assert_almost_eq(polygon.perimeter(), triangle.perimeter());
+    }
+
+    #[test]
+    fn polygon_too_few_vertices() {
+        assert!(Polygon::new(vec![]).is_err());
+        assert!(Polygon::new(vec![(0.0, 0.0), (1.0, 1.0)]).is_err());
+    }
+
+    #[test]
+    fn sector_quarter() {
+        let sector = Sector::new(2.0, PI / 2.0).unwrap();
+        assert_almost_eq(sector.area(), PI);
+        assert_almost_eq(sector.perimeter(), PI + 4.0);
+    }
+
+    #[test]
+    fn sector_full_circle() {
+        let sector = Sector::new(5.0, 2.0 * PI).unwrap();
+        let circle = Circle::new(5.0);
+        assert_almost_eq(sector.area(), circle.area());
+        assert_almost_eq(sector.perimeter(), circle.perimeter() + 10.0);
+    }
+
+    #[test]
+    fn sector_negative() {
+        assert!(Sector::new(-1.0, PI).is_err());
+        assert!(Sector::new(1.0, -PI).is_err());
+        assert!(Sector::new(0.0, 0.0).is_ok());
+    }
+
+    #[test]
+    fn approx_eq_near() {
+        let circle = Circle::with_center((1.0, 2.0), 3.0);
+        assert!(circle.approx_eq(&Circle::with_center((1.0005, 1.9995), 3.0005), 0.001));
+        assert!(Circle::new(0.1 + 0.2).approx_eq(&Circle::new(0.3), 1e-9));
+
+        let rectangle = Rectangle::new(5.0, 3.0);
+        assert!(rectangle.approx_eq(&Rectangle::new(5.0 + 1e-10, 3.0 - 1e-10), 1e-9));
+        assert!(rectangle.approx_eq(&rectangle, 0.0));
+    }
+
+    #[test]
+    fn approx_eq_unequal() {
+        let circle = Circle::with_center((1.0, 2.0), 3.0);
+        assert!(!circle.approx_eq(&Circle::with_center((1.0, 2.0), 3.1), 0.01));
+        assert!(!circle.approx_eq(&Circle::with_center((-1.0, 2.0), 3.0), 0.01));
+
+        // Rectangles with swapped sides have the same area, but different parameters
+        let rectangle = Rectangle::new(5.0, 3.0);
+        assert!(!rectangle.approx_eq(&Rectangle::new(3.0, 5.0), 0.01));
+        assert!(!rectangle.approx_eq(&Rectangle::new(5.0, 3.5), 0.1));
+    }
+
+    #[test]
+    fn shapes_area_approx() {
+        let square = Rectangle::new(PI.sqrt(), PI.sqrt());
+        assert!(shapes_area_approx_eq(&Circle::new(1.0), &square, 1e-9));
+        assert!(shapes_area_approx_eq(
+            &Rectangle::new(5.0, 3.0),
+            &Rectangle::new(3.0, 5.0),
+            0.0
+        ));
+        assert!(!shapes_area_approx_eq(
+            &Circle::new(1.0),
+            &Rectangle::new(1.0, 1.0),
+            0.1
+        ));
+        assert!(!shapes_area_approx_eq(
+            &Ellipse::new(2.0, 1.0),
+            &Circle::new(1.0),
+            1.0
+        ));
+    }
+
+    #[test]
+    fn unit_sphere() {
+        let sphere = Sphere::new(1.0);
+        assert_almost_eq(sphere.volume(), 4.18879);
+        assert_almost_eq(sphere.surface_area(), 4.0 * PI);
+    }
+
+    #[test]
+    fn cube() {
+        let cube = Cuboid::new(1.0, 1.0, 1.0);
+        assert_almost_eq(cube.volume(), 1.0);
+        assert_almost_eq(cube.surface_area(), 6.0);
+
+        let cuboid = Cuboid::new(2.0, 3.0, 4.0);
+        assert_almost_eq(cuboid.volume(), 24.0);
+        assert_almost_eq(cuboid.surface_area(), 52.0);
+    }
+
+    #[test]
+    fn solids_total_volume() {
+        assert_almost_eq(total_volume(&[]), 0.0);
+        let solids: Vec<Box<dyn Solid>> = vec![
+            Box::new(Sphere::new(1.0)),
+            Box::new(Cuboid::new(1.0, 1.0, 1.0)),
+            Box::new(Cuboid::new(2.0, 3.0, 4.0)),
+        ];
+        assert_almost_eq(total_volume(&solids), 4.0 / 3.0 * PI + 25.0);
+    }
+
+    #[test]
+    fn largest_area() {
+        assert!(largest_by_area(&[]).is_none());
+        let shapes: Vec<Box<dyn Shape>> = vec![
+            Box::new(Circle::new(1.0)),
+            Box::new(Rectangle::new(f64::NAN, 1.0)),
+            Box::new(Rectangle::new(1.0, 6.0)),
+            Box::new(Triangle::new(3.0, 4.0, 5.0)),
+            Box::new(Rectangle::new(2.0, 3.0)),
+        ];
+        let largest = largest_by_area(&shapes).unwrap();
+        assert_almost_eq(largest.area(), 6.0);
+        // The first of the equally large shapes is returned
+        assert_almost_eq(largest.perimeter(), 14.0);
+    }
+
+    #[test]
+    fn largest_area_nan() {
+        let shapes: Vec<Box<dyn Shape>> = vec![Box::new(Rectangle::new(f64::NAN, 1.0))];
+        assert!(largest_by_area(&shapes).is_none());
+
+        let shapes: Vec<Box<dyn Shape>> = vec![
+            Box::new(Rectangle::new(f64::NAN, 1.0)),
+            Box::new(Circle::new(2.0)),
+        ];
+        assert_almost_eq(largest_by_area(&shapes).unwrap().area(), 4.0 * PI);
+    }
+
+    #[test]
+    fn bounding_circle_degenerate() {
+        let circle = bounding_circle(&[]);
+        assert_eq!(circle.center(), (0.0, 0.0));
+        assert_almost_eq(circle.area(), 0.0);
+
+        let circle = bounding_circle(&[(3.0, -2.0)]);
+        assert_eq!(circle.center(), (3.0, -2.0));
+        assert_almost_eq(circle.perimeter(), 0.0);
+    }
+
+    #[test]
+    fn bounding_circle_collinear() {
+        let circle = bounding_circle(&[(1.0, 0.0), (0.0, 0.0), (4.0, 0.0), (3.0, 0.0)]);
+        assert_almost_eq(circle.center().0, 2.0);
+        assert_almost_eq(circle.center().1, 0.0);
+        assert_almost_eq(circle.area(), 4.0 * PI);
+        assert_almost_eq(circle.perimeter(), 4.0 * PI);
+    }
+
+    #[test]
+    fn bounding_circle_square() {
+        let circle = bounding_circle(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]);
+        assert_almost_eq(circle.center().0, 1.0);
+        assert_almost_eq(circle.center().1, 1.0);
+        assert_almost_eq(circle.area(), 2.0 * PI);
+        assert_almost_eq(circle.perimeter(), 2.0 * 2f64.sqrt() * PI);
+    }
+
+    #[test]
+    fn bounding_circle_triangle() {
+        // Obtuse triangle, the circle is determined by the longest side only
+        let circle = bounding_circle(&[(0.0, 0.0), (1.0, 0.5), (4.0, 0.0)]);
+        assert_almost_eq(circle.center().0, 2.0);
+        assert_almost_eq(circle.center().1, 0.0);
+        assert_almost_eq(circle.area(), 4.0 * PI);
+
+        // Equilateral triangle, all points are on the boundary
+        let circle = bounding_circle(&[(0.0, 0.0), (2.0, 0.0), (1.0, 3f64.sqrt())]);
+        assert_almost_eq(circle.center().0, 1.0);
+        assert_almost_eq(circle.center().1, 3f64.sqrt() / 3.0);
+        assert_almost_eq(circle.perimeter(), 2.0 * PI * 2.0 / 3f64.sqrt());
+    }
+
     #[track_caller]
     fn assert_almost_eq(value: f64, expected: f64) {
         assert!(
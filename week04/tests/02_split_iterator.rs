@@ -6,40 +6,104 @@
 //! The struct should act as an iterator which iterates over all substrings of the input, separated
 //! by the delimiter. The iterator should never return an empty string; it should automatically skip
 //! over empty strings.
+use std::iter::FusedIterator;
+use std::ops::Range;
+
 struct SplitItems<'a> {
+    /// The whole input passed to [`SplitItems::new`]
+    input: &'a str,
     slice: &'a str,
     delimiter: char,
+    /// Result of [`split_next`] on `slice` computed by [`SplitItems::peek`]
+    peeked: Option<(Option<&'a str>, &'a str)>,
 }
 
 impl<'a> SplitItems<'a> {
     fn new(slice: &'a str, delimiter: char) -> Self {
-        SplitItems { slice, delimiter }
+        SplitItems {
+            input: slice,
+            slice,
+            delimiter,
+            peeked: None,
+        }
+    }
+
+    /// Returns the part of the input that has not been consumed by the iterator yet.
+    /// A peeked item is not consumed.
+    fn remainder(&self) -> &'a str {
+        self.slice
     }
+
+    /// Returns the item that the next call to `next` will return, without consuming it.
+    fn peek(&mut self) -> Option<&'a str> {
+        let (slice, delimiter) = (self.slice, self.delimiter);
+        self.peeked
+            .get_or_insert_with(|| split_next(slice, delimiter))
+            .0
+    }
+
+    /// Returns the byte ranges of the remaining items within the original input instead of the
+    /// items themselves, so that `&input[range]` is the item.
+    fn ranges(self) -> impl Iterator<Item = Range<usize>> + 'a {
+        let input = self.input.as_ptr() as usize;
+        self.map(move |item| {
+            let start = item.as_ptr() as usize - input;
+            start..start + item.len()
+        })
+    }
+}
+
+/// Splits the first non-empty item off the `slice`.
+/// Returns the item (if any) and the rest of the slice after it.
+fn split_next(slice: &str, delimiter: char) -> (Option<&str>, &str) {
+    let slice = slice.trim_start_matches(delimiter);
+    if slice.is_empty() {
+        return (None, slice);
+    }
+    let last = slice.find(delimiter).unwrap_or(slice.len());
+    (Some(&slice[..last]), &slice[last..])
 }
 
 impl<'a> Iterator for SplitItems<'a> {
     type Item = &'a str;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.slice = self.slice.trim_start_matches(self.delimiter);
-        if self.slice.is_empty() {
-            return None;
+        let (result, rest) = self
+            .peeked
+            .take()
+            .unwrap_or_else(|| split_next(self.slice, self.delimiter));
+        self.slice = rest;
+        result
+    }
+
+    fn count(self) -> usize {
+        count_items(self.slice, self.delimiter)
+    }
+}
+
+/// Returns the number of non-empty items of `slice` separated by `delimiter`.
+/// Walks the string once without allocating.
+fn count_items(slice: &str, delimiter: char) -> usize {
+    let mut count = 0;
+    let mut in_item = false;
+    for c in slice.chars() {
+        let is_item = c != delimiter;
+        if is_item && !in_item {
+            count += 1;
         }
-        let last = self
-            .slice
-            .chars()
-            .position(|c| c == self.delimiter)
-            .unwrap_or(self.slice.len());
-        let result = &self.slice[..last];
-        self.slice = &self.slice[last..];
-        Some(result)
+        in_item = is_item;
     }
+    count
 }
 
+/// Once the slice is exhausted, it stays empty, so `next` keeps returning `None`.
+impl FusedIterator for SplitItems<'_> {}
+
 /// Below you can find a set of unit tests.
 #[cfg(test)]
 mod tests {
-    use crate::SplitItems;
+    use crate::{count_items, SplitItems};
+    use std::iter::FusedIterator;
 
     #[test]
     fn split_empty() {
@@ -113,4 +177,118 @@ mod tests {
         let result: SplitItems<'_> = SplitItems::new("foo bar baz", ' ');
         assert_eq!(result.collect::<Vec<_>>(), vec!["foo", "bar", "baz"]);
     }
+
+    #[test]
+    fn split_fused() {
+        fn assert_fused<I: FusedIterator>(_: &I) {}
+
+        let mut iter = SplitItems::new("a b", ' ');
+        assert_fused(&iter);
+        assert_eq!(iter.next(), Some("a"));
+        assert_eq!(iter.next(), Some("b"));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn split_remainder() {
+        let mut iter = SplitItems::new("foo bar  baz", ' ');
+        assert_eq!(iter.remainder(), "foo bar  baz");
+        assert_eq!(iter.next(), Some("foo"));
+        assert_eq!(iter.remainder(), " bar  baz");
+        assert_eq!(iter.next(), Some("bar"));
+        assert_eq!(iter.remainder(), "  baz");
+        assert_eq!(iter.next(), Some("baz"));
+        assert_eq!(iter.remainder(), "");
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.remainder(), "");
+    }
+
+    #[test]
+    fn split_peek() {
+        let mut split = SplitItems::new("  a bc  d", ' ');
+        assert_eq!(split.peek(), Some("a"));
+        assert_eq!(split.peek(), Some("a"));
+        assert_eq!(split.remainder(), "  a bc  d");
+        assert_eq!(split.next(), Some("a"));
+
+        assert_eq!(split.peek(), Some("bc"));
+        assert_eq!(split.remainder(), " bc  d");
+        assert_eq!(split.next(), Some("bc"));
+        assert_eq!(split.next(), Some("d"));
+    }
+
+    #[test]
+    fn split_peek_end() {
+        let mut split = SplitItems::new("a  ", ' ');
+        assert_eq!(split.next(), Some("a"));
+        assert_eq!(split.peek(), None);
+        assert_eq!(split.next(), None);
+        assert_eq!(split.peek(), None);
+
+        assert_eq!(SplitItems::new("", ' ').peek(), None);
+    }
+
+    #[test]
+    fn split_count() {
+        let inputs = [
+            ("", ' '),
+            ("c", 'c'),
+            ("ccc", 'c'),
+            ("  asd", ' '),
+            ("asd  ", ' '),
+            ("a b c d e", ' '),
+            ("   abc   bde casdqw dee xe ", ' '),
+            ("pppabcpppbdepcasdqwpdeepxep", 'p'),
+            ("a,b,,c", ','),
+        ];
+        for (input, delimiter) in inputs {
+            let expected = SplitItems::new(input, delimiter).collect::<Vec<_>>().len();
+            assert_eq!(count_items(input, delimiter), expected, "{input:?}");
+            assert_eq!(
+                SplitItems::new(input, delimiter).count(),
+                expected,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn split_count_partially_consumed() {
+        let mut iter = SplitItems::new("foo bar  baz", ' ');
+        iter.next();
+        assert_eq!(iter.count(), 2);
+    }
+
+    #[test]
+    fn split_ranges() {
+        let input = "  abc   bde x ";
+        let ranges = SplitItems::new(input, ' ').ranges().collect::<Vec<_>>();
+        assert_eq!(ranges, vec![2..5, 8..11, 12..13]);
+        let items = ranges.into_iter().map(|r| &input[r]).collect::<Vec<_>>();
+        assert_eq!(items, vec!["abc", "bde", "x"]);
+
+        assert_eq!(SplitItems::new("", ' ').ranges().count(), 0);
+        assert_eq!(SplitItems::new("ccc", 'c').ranges().count(), 0);
+    }
+
+    #[test]
+    fn split_ranges_partially_consumed() {
+        let input = "foo bar baz";
+        let mut iter = SplitItems::new(input, ' ');
+        iter.next();
+        assert_eq!(iter.peek(), Some("bar"));
+        assert_eq!(iter.ranges().collect::<Vec<_>>(), vec![4..7, 8..11]);
+    }
+
+    #[test]
+    fn split_ranges_multibyte() {
+        let input = "éaééčbcéž";
+        let ranges = SplitItems::new(input, 'é').ranges().collect::<Vec<_>>();
+        let items = ranges.iter().map(|r| &input[r.clone()]).collect::<Vec<_>>();
+        assert_eq!(items, vec!["a", "čbc", "ž"]);
+        assert_eq!(ranges, vec![2..3, 7..11, 13..15]);
+        assert_eq!(items, SplitItems::new(input, 'é').collect::<Vec<_>>());
+    }
 }
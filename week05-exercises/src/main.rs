@@ -1,4 +1,4 @@
-use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::{DisableFocusChange, EnableFocusChange, Event, KeyEventKind};
 use ratatui::text::Line;
 use ratatui::widgets::{BorderType, Borders, Paragraph};
 use ratatui::{
@@ -11,8 +11,9 @@ use ratatui::{
     DefaultTerminal,
 };
 use std::collections::VecDeque;
-use std::thread::sleep;
-use std::time::Duration;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 // 1 (warm-up): Figure out how many cores does your PC have (with Rust code :)) and print it
 // You don't need an external crate for it, there is a function for it in the stdlib
@@ -36,18 +37,71 @@ use std::time::Duration;
 // Warning: if you want to draw the per-cope "htop progress bars" with ratatui, don't combine
 // other coloring crates with Ratatui; use Ratatui's colors and styles instead.
 
+/// Title shown on the chart while the average usage is above the alert threshold.
+const ALERT_TITLE: &str = " ⚠ HIGH LOAD ";
+
+/// Interval between two samples while the terminal has focus.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the monitor waits for events at once while sampling is paused.
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether the monitor is sampling, driven by the focus events of the terminal.
+/// Terminals that do not report focus changes never pause the sampling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sampling {
+    /// Sample and redraw every [`SAMPLE_INTERVAL`]
+    Active,
+    /// The terminal lost focus, only wait for events
+    Paused,
+}
+
+impl Sampling {
+    /// Returns the sampling state after the terminal `event`.
+    fn on_event(self, event: &Event) -> Self {
+        match event {
+            Event::FocusLost => Sampling::Paused,
+            Event::FocusGained => Sampling::Active,
+            _ => self,
+        }
+    }
+}
+
+/// Command line arguments of the monitor.
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    /// `--alert-threshold <percent>`
+    alert_threshold: Option<f64>,
+    /// `--metrics-port <port>`
+    metrics_port: Option<u16>,
+}
+
 pub struct App {
     collector: psutil::cpu::CpuPercentCollector,
     terminal: DefaultTerminal,
     usages: VecDeque<(f64, f64)>,
+    /// Average usage (in %) above which the chart shows an alert
+    alert_threshold: Option<f64>,
+    /// Metrics of the latest sample served by the metrics endpoint, if it is enabled
+    metrics: Option<Arc<Mutex<String>>>,
 }
 
 impl App {
-    fn new() -> anyhow::Result<Self> {
+    fn new(args: Args) -> anyhow::Result<Self> {
+        // Everything that can fail is done before the terminal is taken over, so that errors are
+        // readable and the terminal is not left in raw mode
+        let metrics = args.metrics_port.map(serve_metrics).transpose()?;
+        let collector = psutil::cpu::CpuPercentCollector::new()?;
+        // Restore the terminal before the panic message is printed, even if `Drop` is not reached
+        chain_panic_hook(restore_terminal);
+        let terminal = ratatui::init();
+        // Without focus reporting, no focus events arrive and the sampling is never paused
+        let _ = ratatui::crossterm::execute!(std::io::stdout(), EnableFocusChange);
         Ok(Self {
-            collector: psutil::cpu::CpuPercentCollector::new()?,
-            terminal: ratatui::init(),
+            collector,
+            terminal,
             usages: VecDeque::with_capacity(120),
+            alert_threshold: args.alert_threshold,
+            metrics,
         })
     }
 
@@ -56,10 +110,16 @@ impl App {
         let mut medium_usage: Vec<(f64, f64)> = Vec::with_capacity(120);
         let mut high_usage: Vec<(f64, f64)> = Vec::with_capacity(120);
         let cpu_count = std::thread::available_parallelism()?;
+        let mut sampling = Sampling::Active;
+        // Error of the last failed snapshot, shown until a snapshot succeeds
+        let mut snapshot_error: Option<String> = None;
 
         loop {
             let cpu_percent = self.collector.cpu_percent()? as f64;
             let cpu_percent_percpu = self.collector.cpu_percent_percpu()?;
+            if let Some(metrics) = &self.metrics {
+                *metrics.lock().unwrap() = render_metrics(&cpu_percent_percpu, cpu_percent);
+            }
             low_usage.clear();
             medium_usage.clear();
             high_usage.clear();
@@ -82,10 +142,12 @@ impl App {
                 }
             });
 
+            let (border_style, alert_title) = chart_border(cpu_percent, self.alert_threshold);
+
             self.terminal.clear()?;
             self.terminal.draw(|frame| {
                 let [left, right] =
-                    Layout::horizontal([Constraint::Length(120 + 7), Constraint::Length(40)])
+                    Layout::horizontal([Constraint::Length(120 + 7), Constraint::Length(50)])
                         .areas(frame.area());
 
                 let x_labels = vec![
@@ -110,14 +172,18 @@ impl App {
                         .data(&high_usage),
                 ];
 
+                let mut block = Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .white()
+                    .border_style(border_style)
+                    .title_alignment(Alignment::Center)
+                    .title_bottom(format!(" CPU usage: {:.2} % ", cpu_percent));
+                if let Some(title) = alert_title {
+                    block = block.title(Line::from(title).red().bold());
+                }
+
                 let chart = Chart::new(datasets)
-                    .block(
-                        Block::bordered()
-                            .border_type(BorderType::Rounded)
-                            .white()
-                            .title_alignment(Alignment::Center)
-                            .title_bottom(format!(" CPU usage: {:.2} % ", cpu_percent)),
-                    )
+                    .block(block)
                     .x_axis(
                         Axis::default()
                             .labels_alignment(Alignment::Right)
@@ -152,44 +218,329 @@ impl App {
                         Span::styled(" [".to_string(), Style::default().fg(Color::White)),
                         inner,
                         Span::styled(
-                            format!("{}]\n", ".".repeat(10 - ratio)),
+                            format!("{}]", ".".repeat(10 - ratio)),
+                            Style::default().fg(Color::White),
+                        ),
+                        Span::styled(
+                            format!(" {:>8}\n", format_ghz(cpu_frequency_khz(i))),
                             Style::default().fg(Color::White),
                         ),
                     ]));
                 }
 
-                let p = Paragraph::new(lines).alignment(Alignment::Center).block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_style(Style::default().fg(Color::White))
-                        .title_bottom(format!(" {} CPUs ", cpu_count))
-                        .title_alignment(Alignment::Center)
-                        .border_type(BorderType::Rounded),
-                );
+                let mut block = Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::White))
+                    .title_bottom(format!(" {} CPUs ", cpu_count))
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded);
+                if let Some(error) = &snapshot_error {
+                    block = block.title(Line::from(format!(" Snapshot failed: {error} ")).red());
+                }
+                let p = Paragraph::new(lines)
+                    .alignment(Alignment::Center)
+                    .block(block);
                 frame.render_widget(p, right);
             })?;
-            sleep(Duration::from_secs(1));
-            if event::poll(Duration::ZERO)? {
-                if let event::Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press && key.code == event::KeyCode::Char('q') {
+
+            // Handle events until the next sample is due, while paused there is no next sample
+            let next_sample = Instant::now() + SAMPLE_INTERVAL;
+            loop {
+                let timeout = match sampling {
+                    Sampling::Active => next_sample.saturating_duration_since(Instant::now()),
+                    Sampling::Paused => PAUSED_POLL_INTERVAL,
+                };
+                if !event::poll(timeout)? {
+                    if sampling == Sampling::Active {
                         break;
                     }
+                    continue;
+                }
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                        event::KeyCode::Char('q') => return Ok(()),
+                        event::KeyCode::Char('s') => {
+                            let path = chrono::Local::now()
+                                .format("cpu-snapshot-%Y%m%d-%H%M%S.txt")
+                                .to_string();
+                            // A failed snapshot is only reported, it does not stop the sampling
+                            snapshot_error = std::fs::write(
+                                path,
+                                render_table(&cpu_percent_percpu, cpu_percent),
+                            )
+                            .err()
+                            .map(|error| error.to_string());
+                        }
+                        _ => {}
+                    },
+                    event => {
+                        let previous = sampling;
+                        sampling = sampling.on_event(&event);
+                        // Sample right away once the terminal regains focus
+                        if previous == Sampling::Paused && sampling == Sampling::Active {
+                            break;
+                        }
+                    }
                 }
             }
         }
-        Ok(())
     }
 }
 
+/// Returns the border style of the chart for the average `usage`, and the alert title to show.
+/// Usage above the `alert_threshold` turns the border red, otherwise it stays white.
+fn chart_border(usage: f64, alert_threshold: Option<f64>) -> (Style, Option<&'static str>) {
+    if alert_threshold.is_some_and(|threshold| usage > threshold) {
+        (Style::default().fg(Color::Red), Some(ALERT_TITLE))
+    } else {
+        (Style::default().fg(Color::White), None)
+    }
+}
+
+/// Parses the command line arguments, the optional `--alert-threshold <percent>` and
+/// `--metrics-port <port>`.
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Args> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--alert-threshold" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value after --alert-threshold"))?;
+                parsed.alert_threshold = Some(value.parse::<f64>()?);
+            }
+            "--metrics-port" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value after --metrics-port"))?;
+                parsed.metrics_port = Some(value.parse::<u16>()?);
+            }
+            _ => anyhow::bail!("Unknown argument `{arg}`"),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Starts an HTTP server on `127.0.0.1:<port>` in a background thread, which responds to every
+/// request with the metrics text stored in the returned mutex.
+fn serve_metrics(port: u16) -> anyhow::Result<Arc<Mutex<String>>> {
+    let server = tiny_http::Server::http(("127.0.0.1", port))
+        .map_err(|error| anyhow::anyhow!("Cannot start the metrics server: {error}"))?;
+    let metrics = Arc::new(Mutex::new(String::new()));
+    let served = metrics.clone();
+    std::thread::spawn(move || {
+        let header: tiny_http::Header = "Content-Type: text/plain; version=0.0.4".parse().unwrap();
+        for request in server.incoming_requests() {
+            let body = served.lock().unwrap().clone();
+            let response = tiny_http::Response::from_string(body).with_header(header.clone());
+            let _ = request.respond(response);
+        }
+    });
+    Ok(metrics)
+}
+
+/// Renders the average and per-core usages as gauges in the Prometheus text format.
+fn render_metrics(cpu_percent_percpu: &[f32], cpu_percent: f64) -> String {
+    let mut metrics = String::new();
+    metrics.push_str("# HELP cpu_usage_percent Average CPU usage over all cores.\n");
+    metrics.push_str("# TYPE cpu_usage_percent gauge\n");
+    writeln!(metrics, "cpu_usage_percent {cpu_percent}").unwrap();
+    metrics.push_str("# HELP cpu_core_usage_percent CPU usage of a single core.\n");
+    metrics.push_str("# TYPE cpu_core_usage_percent gauge\n");
+    for (i, util) in cpu_percent_percpu.iter().enumerate() {
+        writeln!(metrics, "cpu_core_usage_percent{{core=\"{i}\"}} {util}").unwrap();
+    }
+    metrics
+}
+
+/// Renders the per-core usages and their average as a plain text table.
+fn render_table(cpu_percent_percpu: &[f32], cpu_percent: f64) -> String {
+    let mut table = String::from("CPU      Usage\n");
+    for (i, util) in cpu_percent_percpu.iter().enumerate() {
+        writeln!(table, "{:<8} {util:6.2} %", format!("CPU{i}")).unwrap();
+    }
+    writeln!(table, "{:<8} {cpu_percent:6.2} %", "Average").unwrap();
+    table
+}
+
+/// Returns the current clock speed of the given `core` in kHz, if the platform exposes it.
+#[cfg(target_os = "linux")]
+fn cpu_frequency_khz(core: usize) -> Option<u64> {
+    let path = format!("/sys/devices/system/cpu/cpu{core}/cpufreq/scaling_cur_freq");
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Returns the current clock speed of the given `core` in kHz, if the platform exposes it.
+#[cfg(not(target_os = "linux"))]
+fn cpu_frequency_khz(_core: usize) -> Option<u64> {
+    None
+}
+
+/// Formats a frequency given in kHz as GHz, or `N/A` when the frequency is unknown.
+fn format_ghz(frequency_khz: Option<u64>) -> String {
+    match frequency_khz {
+        Some(frequency) => format!("{:.2} GHz", frequency as f64 / 1_000_000.0),
+        None => "N/A".to_string(),
+    }
+}
+
+/// Installs a panic hook that calls `before` and then delegates to the previously installed hook.
+fn chain_panic_hook(before: impl Fn() + Send + Sync + 'static) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        before();
+        previous(info);
+    }));
+}
+
+/// Disables the focus reporting and restores the terminal.
+fn restore_terminal() {
+    let _ = ratatui::crossterm::execute!(std::io::stdout(), DisableFocusChange);
+    ratatui::restore();
+}
+
 impl Drop for App {
     fn drop(&mut self) {
-        ratatui::restore();
+        restore_terminal();
     }
 }
 
 fn main() {
-    if let Err(e) = App::new().and_then(|mut app| app.run()) {
+    if let Err(e) = parse_args(std::env::args().skip(1))
+        .and_then(App::new)
+        .and_then(|mut app| app.run())
+    {
         eprintln!("Error: {}", e);
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chain_panic_hook, chart_border, format_ghz, parse_args, render_metrics, render_table, Args,
+        Sampling, ALERT_TITLE,
+    };
+    use ratatui::crossterm::event::{Event, KeyCode, KeyEvent};
+    use ratatui::style::{Color, Style};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn table() {
+        assert_eq!(
+            render_table(&[12.5, 100.0, 0.0], 37.5),
+            "CPU      Usage
+CPU0      12.50 %
+CPU1     100.00 %
+CPU2       0.00 %
+Average   37.50 %
+"
+        );
+    }
+
+    #[test]
+    fn table_no_cpus() {
+        assert_eq!(
+            render_table(&[], 0.0),
+            "CPU      Usage\nAverage    0.00 %\n"
+        );
+    }
+
+    #[test]
+    fn metrics() {
+        assert_eq!(
+            render_metrics(&[12.5, 100.0, 0.0], 37.5),
+            r#"# HELP cpu_usage_percent Average CPU usage over all cores.
+# TYPE cpu_usage_percent gauge
+cpu_usage_percent 37.5
+# HELP cpu_core_usage_percent CPU usage of a single core.
+# TYPE cpu_core_usage_percent gauge
+cpu_core_usage_percent{core="0"} 12.5
+cpu_core_usage_percent{core="1"} 100
+cpu_core_usage_percent{core="2"} 0
+"#
+        );
+    }
+
+    #[test]
+    fn args() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&[]).unwrap(), Args::default());
+        assert_eq!(
+            args(&["--metrics-port", "9100", "--alert-threshold", "80"]).unwrap(),
+            Args {
+                alert_threshold: Some(80.0),
+                metrics_port: Some(9100),
+            }
+        );
+        assert!(args(&["--metrics-port"]).is_err());
+        assert!(args(&["--metrics-port", "70000"]).is_err());
+    }
+
+    #[test]
+    fn ghz() {
+        assert_eq!(format_ghz(Some(3_400_000)), "3.40 GHz");
+        assert_eq!(format_ghz(Some(800_000)), "0.80 GHz");
+        assert_eq!(format_ghz(Some(2_995_000)), "3.00 GHz");
+        assert_eq!(format_ghz(None), "N/A");
+    }
+
+    #[test]
+    fn alert_border() {
+        let normal = (Style::default().fg(Color::White), None);
+        let alert = (Style::default().fg(Color::Red), Some(ALERT_TITLE));
+        assert_eq!(chart_border(50.0, Some(80.0)), normal);
+        assert_eq!(chart_border(80.0, Some(80.0)), normal);
+        assert_eq!(chart_border(80.5, Some(80.0)), alert);
+        assert_eq!(chart_border(100.0, Some(0.0)), alert);
+        // Without a threshold, there is never an alert
+        assert_eq!(chart_border(100.0, None), normal);
+    }
+
+    #[test]
+    fn focus_sampling() {
+        let key = Event::Key(KeyEvent::from(KeyCode::Char('x')));
+        let sampling = Sampling::Active;
+        assert_eq!(sampling.on_event(&key), Sampling::Active);
+        assert_eq!(sampling.on_event(&Event::FocusGained), Sampling::Active);
+        assert_eq!(sampling.on_event(&Event::Resize(80, 24)), Sampling::Active);
+
+        let sampling = sampling.on_event(&Event::FocusLost);
+        assert_eq!(sampling, Sampling::Paused);
+        assert_eq!(sampling.on_event(&key), Sampling::Paused);
+        assert_eq!(sampling.on_event(&Event::FocusLost), Sampling::Paused);
+        assert_eq!(sampling.on_event(&Event::FocusGained), Sampling::Active);
+    }
+
+    #[test]
+    fn panic_hook_chained() {
+        // The hook is global, so panics of tests running in other threads are not recorded and
+        // are passed to the original hook instead
+        let original = Arc::new(std::panic::take_hook());
+        let test_thread = std::thread::current().id();
+        let on_test_thread = move || std::thread::current().id() == test_thread;
+        let calls = Arc::new(Mutex::new(vec![]));
+
+        let previous_calls = calls.clone();
+        let previous_original = original.clone();
+        std::panic::set_hook(Box::new(move |info| {
+            if on_test_thread() {
+                previous_calls.lock().unwrap().push("previous");
+            } else {
+                previous_original(info);
+            }
+        }));
+        let restore_calls = calls.clone();
+        chain_panic_hook(move || {
+            if on_test_thread() {
+                restore_calls.lock().unwrap().push("restore");
+            }
+        });
+
+        let result = std::panic::catch_unwind(|| panic!("crash"));
+        std::panic::set_hook(Box::new(move |info| original(info)));
+
+        assert!(result.is_err());
+        assert_eq!(*calls.lock().unwrap(), vec!["restore", "previous"]);
+    }
+}
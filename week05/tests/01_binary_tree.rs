@@ -25,6 +25,7 @@
 //! the whole tree into a Vec and call that an iterator.
 
 use std::cmp::Ordering;
+use std::fmt::Display;
 
 #[derive(Debug, PartialEq)]
 enum BinaryTree<T> {
@@ -64,6 +65,57 @@ impl<T> BinaryTree<T> {
         inner(self, &mut f)
     }
 
+    /// Builds a new tree with the same shape, applying `f` to each value.
+    /// The result does not have to be a valid search tree if `f` is not monotonic.
+    fn map<U, F: FnMut(&T) -> U>(&self, mut f: F) -> BinaryTree<U> {
+        fn inner<T, U, F: FnMut(&T) -> U>(tree: &BinaryTree<T>, f: &mut F) -> BinaryTree<U> {
+            match tree {
+                BinaryTree::Leaf => BinaryTree::Leaf,
+                BinaryTree::Node { value, left, right } => BinaryTree::Node {
+                    value: f(value),
+                    left: Box::new(inner(left, f)),
+                    right: Box::new(inner(right, f)),
+                },
+            }
+        }
+
+        inner(self, &mut f)
+    }
+
+    /// Serializes the tree in preorder, with an explicit `None` for every leaf.
+    fn to_preorder_with_leaves(&self) -> Vec<Option<T>>
+    where
+        T: Clone,
+    {
+        fn inner<T: Clone>(tree: &BinaryTree<T>, data: &mut Vec<Option<T>>) {
+            match tree {
+                BinaryTree::Leaf => data.push(None),
+                BinaryTree::Node { value, left, right } => {
+                    data.push(Some(value.clone()));
+                    inner(left, data);
+                    inner(right, data);
+                }
+            }
+        }
+
+        let mut data = Vec::with_capacity(2 * self.size() + 1);
+        inner(self, &mut data);
+        data
+    }
+
+    /// Rebuilds a tree serialized by [`BinaryTree::to_preorder_with_leaves`], consuming exactly the
+    /// items that belong to the tree. Missing items (an exhausted iterator) are treated as leaves.
+    fn from_preorder_with_leaves(data: &mut impl Iterator<Item = Option<T>>) -> BinaryTree<T> {
+        match data.next().flatten() {
+            None => BinaryTree::Leaf,
+            Some(value) => {
+                let left = Box::new(BinaryTree::from_preorder_with_leaves(data));
+                let right = Box::new(BinaryTree::from_preorder_with_leaves(data));
+                BinaryTree::Node { value, left, right }
+            }
+        }
+    }
+
     fn insert(self, item: T) -> BinaryTree<T>
     where
         T: Ord,
@@ -86,6 +138,68 @@ impl<T> BinaryTree<T> {
         }
     }
 
+    /// Inserts all items from `iter` into the tree and returns the resulting tree.
+    fn extend<I: IntoIterator<Item = T>>(self, iter: I) -> BinaryTree<T>
+    where
+        T: Ord,
+    {
+        iter.into_iter().fold(self, BinaryTree::insert)
+    }
+
+    /// Returns a balanced search tree with the values of both trees.
+    /// Values contained in both trees are kept only once (from `self`), like in `insert`.
+    fn merge(self, other: BinaryTree<T>) -> BinaryTree<T>
+    where
+        T: Ord,
+    {
+        /// Moves the values of the tree into `values` in sorted order.
+        fn into_sorted<T>(tree: BinaryTree<T>, values: &mut Vec<T>) {
+            if let BinaryTree::Node { value, left, right } = tree {
+                into_sorted(*left, values);
+                values.push(value);
+                into_sorted(*right, values);
+            }
+        }
+
+        /// Builds a balanced tree from the next `count` sorted values.
+        fn balanced<T>(values: &mut impl Iterator<Item = T>, count: usize) -> BinaryTree<T> {
+            if count == 0 {
+                return BinaryTree::Leaf;
+            }
+            let left = Box::new(balanced(values, count / 2));
+            let value = values.next().expect("Not enough values");
+            let right = Box::new(balanced(values, count - count / 2 - 1));
+            BinaryTree::Node { value, left, right }
+        }
+
+        let (mut first, mut second) = (vec![], vec![]);
+        into_sorted(self, &mut first);
+        into_sorted(other, &mut second);
+
+        let mut merged = Vec::with_capacity(first.len() + second.len());
+        let mut first = first.into_iter().peekable();
+        let mut second = second.into_iter().peekable();
+        loop {
+            let value = match (first.peek(), second.peek()) {
+                (Some(a), Some(b)) => match a.cmp(b) {
+                    Ordering::Less => first.next(),
+                    Ordering::Equal => {
+                        second.next();
+                        first.next()
+                    }
+                    Ordering::Greater => second.next(),
+                },
+                (Some(_), None) => first.next(),
+                (None, Some(_)) => second.next(),
+                (None, None) => break,
+            };
+            merged.extend(value);
+        }
+
+        let count = merged.len();
+        balanced(&mut merged.into_iter(), count)
+    }
+
     fn contains(&self, item: &T) -> bool
     where
         T: Ord,
@@ -100,6 +214,104 @@ impl<T> BinaryTree<T> {
         }
     }
 
+    /// Returns the smallest value in the tree that is strictly greater than `item`.
+    fn successor(&self, item: &T) -> Option<&T>
+    where
+        T: Ord,
+    {
+        let mut tree = self;
+        let mut best = None;
+        while let BinaryTree::Node { value, left, right } = tree {
+            if value > item {
+                best = Some(value);
+                tree = left;
+            } else {
+                tree = right;
+            }
+        }
+        best
+    }
+
+    /// Returns the largest value in the tree that is strictly smaller than `item`.
+    fn predecessor(&self, item: &T) -> Option<&T>
+    where
+        T: Ord,
+    {
+        let mut tree = self;
+        let mut best = None;
+        while let BinaryTree::Node { value, left, right } = tree {
+            if value < item {
+                best = Some(value);
+                tree = right;
+            } else {
+                tree = left;
+            }
+        }
+        best
+    }
+
+    /// Returns the number of values in the tree that are strictly smaller than `item`.
+    fn rank(&self, item: &T) -> usize
+    where
+        T: Ord,
+    {
+        let mut tree = self;
+        let mut rank = 0;
+        while let BinaryTree::Node { value, left, right } = tree {
+            if value < item {
+                rank += left.size() + 1;
+                tree = right;
+            } else {
+                tree = left;
+            }
+        }
+        rank
+    }
+
+    /// Returns the value at the sorted position `k` (starting from zero).
+    fn select(&self, k: usize) -> Option<&T> {
+        self.iter().nth(k)
+    }
+
+    /// Renders the tree rotated by 90° to the left, one value per line: the right subtree is above
+    /// its parent, the left subtree below it, and each level is indented by four spaces.
+    /// Leaves are omitted.
+    fn to_pretty_string(&self) -> String
+    where
+        T: Display,
+    {
+        self.to_pretty_string_limited(usize::MAX)
+    }
+
+    /// Renders the tree like [`BinaryTree::to_pretty_string`], but only its first `max_depth`
+    /// levels. Deeper non-empty subtrees are shown as `…`.
+    fn to_pretty_string_limited(&self, max_depth: usize) -> String
+    where
+        T: Display,
+    {
+        fn inner<T: Display>(
+            tree: &BinaryTree<T>,
+            depth: usize,
+            max_depth: usize,
+            out: &mut String,
+        ) {
+            if let BinaryTree::Node { value, left, right } = tree {
+                let indent = "    ".repeat(depth);
+                if depth == max_depth {
+                    out.push_str(&format!("{indent}…\n"));
+                    return;
+                }
+                inner(right, depth + 1, max_depth, out);
+                out.push_str(&format!("{indent}{value}\n"));
+                inner(left, depth + 1, max_depth, out);
+            }
+        }
+
+        let mut out = String::new();
+        inner(self, 0, max_depth, &mut out);
+        out
+    }
+
     fn iter(&self) -> impl Iterator<Item = &T> + '_ {
         struct BinaryTreeIterator<'a, T> {
             stack: Vec<&'a BinaryTree<T>>,
@@ -299,6 +511,36 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn map_to_string() {
+        let tree = node(2, node_leaf(1), node(4, node_leaf(3), leaf()));
+        let tree = tree.map(|value| format!("#{value}"));
+        insta::assert_debug_snapshot!(tree, @r###"
+        Node {
+            value: "#2",
+            left: Node {
+                value: "#1",
+                left: Leaf,
+                right: Leaf,
+            },
+            right: Node {
+                value: "#4",
+                left: Node {
+                    value: "#3",
+                    left: Leaf,
+                    right: Leaf,
+                },
+                right: Leaf,
+            },
+        }
+        "###);
+    }
+
+    #[test]
+    fn map_empty() {
+        assert_eq!(leaf::<u32>().map(|value| value * 2), leaf());
+    }
+
     // Bonus tests
     #[test]
     fn iter_empty() {
@@ -358,6 +600,183 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extend_range() {
+        let tree = leaf().extend(0..10);
+        assert_eq!(tree.size(), 10);
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn extend_existing() {
+        let tree = build_tree(&[5, 2]).extend(vec![8, 2, 1, 5]);
+        assert_eq!(tree, build_tree(&[5, 2, 8, 1]));
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&1, &2, &5, &8]);
+    }
+
+    #[test]
+    fn successor() {
+        let tree = leaf().extend([5, 2, 8, 1, 3, 4, 7, 6, 10, 9]);
+        assert_eq!(tree.successor(&5), Some(&6));
+        assert_eq!(tree.successor(&4), Some(&5));
+        assert_eq!(tree.successor(&0), Some(&1));
+        assert_eq!(tree.successor(&10), None);
+        assert_eq!(leaf::<u32>().successor(&1), None);
+    }
+
+    #[test]
+    fn predecessor() {
+        let tree = leaf().extend([5, 2, 8, 1, 3, 4, 7, 6, 10, 9]);
+        assert_eq!(tree.predecessor(&5), Some(&4));
+        assert_eq!(tree.predecessor(&6), Some(&5));
+        assert_eq!(tree.predecessor(&11), Some(&10));
+        assert_eq!(tree.predecessor(&1), None);
+        assert_eq!(leaf::<u32>().predecessor(&1), None);
+    }
+
+    #[test]
+    fn rank() {
+        let tree = leaf().extend([5, 2, 8, 1, 3, 4, 7, 6, 10, 9]);
+        assert_eq!(tree.rank(&1), 0);
+        assert_eq!(tree.rank(&5), 4);
+        assert_eq!(tree.rank(&10), 9);
+        assert_eq!(tree.rank(&0), 0);
+        assert_eq!(tree.rank(&11), 10);
+        assert_eq!(leaf::<u32>().rank(&1), 0);
+    }
+
+    #[test]
+    fn select() {
+        let tree = leaf().extend([5, 2, 8, 1, 3, 4, 7, 6, 10, 9]);
+        assert_eq!(tree.select(0), Some(&1));
+        assert_eq!(tree.select(4), Some(&5));
+        assert_eq!(tree.select(9), Some(&10));
+        assert_eq!(tree.select(10), None);
+        for k in 0..10 {
+            assert_eq!(tree.rank(tree.select(k).unwrap()), k);
+        }
+    }
+
+    #[test]
+    fn merge_disjoint() {
+        let tree = build_tree(&[1, 2, 3, 4, 5]).merge(build_tree(&[10, 8, 6, 9, 7, 11]));
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            (1..=11).collect::<Vec<_>>()
+        );
+        assert_eq!(tree.height(), 4);
+        for k in 0..11 {
+            assert_eq!(tree.rank(tree.select(k).unwrap()), k);
+        }
+
+        assert_eq!(leaf::<u32>().merge(leaf()), leaf());
+        assert_eq!(
+            leaf().merge(build_tree(&[1, 2, 3])),
+            node(2, node_leaf(1), node_leaf(3))
+        );
+    }
+
+    #[test]
+    fn merge_overlapping() {
+        let tree = build_tree(&[5, 2, 8, 1]).merge(build_tree(&[2, 3, 8, 9, 5]));
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 5, 8, 9]
+        );
+        assert_eq!(tree.size(), 6);
+        assert_eq!(tree.height(), 3);
+
+        let tree = build_tree(&[1, 2, 3]);
+        let same = build_tree(&[3, 2, 1]);
+        assert_eq!(tree.merge(same), node(2, node_leaf(1), node_leaf(3)));
+    }
+
+    #[test]
+    fn pretty_string() {
+        let tree = build_tree(&[5, 2, 8, 1, 3, 9, 4]);
+        insta::assert_snapshot!(tree.to_pretty_string(), @r###"
+                9
+            8
+        5
+                    4
+                3
+            2
+                1
+        "###);
+        insta::assert_snapshot!(tree.to_pretty_string_limited(2), @r###"
+                …
+            8
+        5
+                …
+            2
+                …
+        "###);
+        insta::assert_snapshot!(tree.to_pretty_string_limited(0), @"…");
+        insta::assert_snapshot!(leaf::<u32>().to_pretty_string(), @"");
+        insta::assert_snapshot!(node_leaf("a").to_pretty_string(), @"a");
+    }
+
+    #[test]
+    fn successor_missing_value() {
+        let tree = build_tree(&[10, 20, 30]);
+        assert_eq!(tree.successor(&15), Some(&20));
+        assert_eq!(tree.predecessor(&15), Some(&10));
+    }
+
+    #[test]
+    fn preorder_empty() {
+        let tree = leaf::<u32>();
+        let data = tree.to_preorder_with_leaves();
+        assert_eq!(data, vec![None]);
+        assert_eq!(
+            BinaryTree::from_preorder_with_leaves(&mut data.into_iter()),
+            tree
+        );
+    }
+
+    #[test]
+    fn preorder_single() {
+        let tree = node_leaf(5);
+        let data = tree.to_preorder_with_leaves();
+        assert_eq!(data, vec![Some(5), None, None]);
+        assert_eq!(
+            BinaryTree::from_preorder_with_leaves(&mut data.into_iter()),
+            tree
+        );
+    }
+
+    #[test]
+    fn preorder_unbalanced() {
+        let tree = build_tree(&[5, 2, 1, 3, 4, 8]);
+        let data = tree.to_preorder_with_leaves();
+        assert_eq!(
+            data,
+            vec![
+                Some(5),
+                Some(2),
+                Some(1),
+                None,
+                None,
+                Some(3),
+                None,
+                Some(4),
+                None,
+                None,
+                Some(8),
+                None,
+                None
+            ]
+        );
+
+        // Only the items of the tree are consumed
+        let mut iter = data.into_iter().chain([Some(42)]);
+        assert_eq!(BinaryTree::from_preorder_with_leaves(&mut iter), tree);
+        assert_eq!(iter.next(), Some(Some(42)));
+    }
+
     fn leaf<T>() -> BinaryTree<T> {
         BinaryTree::Leaf
     }
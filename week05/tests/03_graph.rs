@@ -17,17 +17,35 @@
 //! Answer: No it is no possible due to ready/finished check in finish method.
 
 use std::cell::RefCell;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 
 /// This is just a type alias, not a newtype.
 /// It can be useful to start with it if you want to give a new name
 /// to an existing type, but don't want to deal with newtype wrapping yet.
 type NodeId = u64;
 
+/// Callback invoked with the ID of a finished node and its dependents that became ready.
+type FinishCallback = Box<dyn FnMut(NodeId, &[NodeId])>;
+
+/// Errors of building a graph with [`Graph::from_edges`].
+#[derive(Debug, Eq, PartialEq)]
+enum GraphError {
+    DuplicateNode(NodeId),
+    UnknownDependency {
+        id: NodeId,
+        dependency: NodeId,
+    },
+    /// The node is on a dependency cycle or depends on one
+    Cycle(NodeId),
+}
+
 #[derive(Default)]
 struct Graph<T> {
     nodes: HashMap<NodeId, Rc<RefCell<Node<T>>>>,
+    /// `finish` only borrows the graph immutably, so the callback needs interior mutability
+    on_finish: RefCell<Option<FinishCallback>>,
 }
 
 /// Single node of the graph
@@ -45,11 +63,66 @@ struct Node<T> {
     value: Option<T>,
     /// This node depends on the following nodes
     dependencies: Vec<Rc<RefCell<Self>>>,
-    /// The following nodes depend on this node
-    dependents: Vec<Rc<RefCell<Self>>>,
+    /// The following nodes depend on this node.
+    /// The links are weak, so that the nodes do not form reference cycles with their dependencies.
+    dependents: Vec<Weak<RefCell<Self>>>,
 }
 
 impl<T> Graph<T> {
+    /// Builds a graph from a list of nodes and their dependencies, which can be in any order.
+    /// The nodes are added in a topological order, so that dependencies always exist first.
+    ///
+    /// Returns an error if a node ID is listed twice, if a dependency is not listed or if the
+    /// dependencies form a cycle (the error contains the first listed node on a cycle or depending
+    /// on one).
+    fn from_edges(edges: &[(NodeId, Vec<NodeId>)]) -> Result<Graph<T>, GraphError> {
+        let mut dependents: HashMap<NodeId, Vec<usize>> = HashMap::new();
+        for (id, _) in edges {
+            if dependents.insert(*id, vec![]).is_some() {
+                return Err(GraphError::DuplicateNode(*id));
+            }
+        }
+        for (index, (id, dependencies)) in edges.iter().enumerate() {
+            for dependency in dependencies {
+                match dependents.get_mut(dependency) {
+                    Some(dependents) => dependents.push(index),
+                    None => {
+                        return Err(GraphError::UnknownDependency {
+                            id: *id,
+                            dependency: *dependency,
+                        })
+                    }
+                }
+            }
+        }
+
+        // Number of dependencies of each node that were not added yet
+        let mut missing: Vec<usize> = edges
+            .iter()
+            .map(|(_, dependencies)| dependencies.len())
+            .collect();
+        let mut queue: VecDeque<usize> = (0..edges.len()).filter(|i| missing[*i] == 0).collect();
+        let mut graph = Graph {
+            nodes: HashMap::with_capacity(edges.len()),
+            on_finish: RefCell::new(None),
+        };
+        while let Some(index) = queue.pop_front() {
+            let (id, dependencies) = &edges[index];
+            graph.add(*id, dependencies.clone());
+            for dependent in &dependents[id] {
+                missing[*dependent] -= 1;
+                if missing[*dependent] == 0 {
+                    queue.push_back(*dependent);
+                }
+            }
+        }
+
+        match missing.iter().position(|missing| *missing > 0) {
+            Some(index) => Err(GraphError::Cycle(edges[index].0)),
+            None => Ok(graph),
+        }
+    }
+
     /// Add a new node to the graph.
     /// The `dependents` links of all the passed `dependencies` should be updated.
     ///
@@ -86,12 +159,26 @@ impl<T> Graph<T> {
         }));
 
         for dependency in &node.borrow().dependencies {
-            dependency.borrow_mut().dependents.push(node.clone());
+            dependency
+                .borrow_mut()
+                .dependents
+                .push(Rc::downgrade(&node));
         }
 
         self.nodes.insert(id, node);
     }
 
+    /// Adds a new node like [`Graph::add`] if there is no node with the given `id` yet.
+    /// Returns true if the node was added. An existing node is left unchanged (even if it has
+    /// different dependencies) and `dependencies` are not checked.
+    fn get_or_add(&mut self, id: NodeId, dependencies: Vec<NodeId>) -> bool {
+        if self.nodes.contains_key(&id) {
+            return false;
+        }
+        self.add(id, dependencies);
+        true
+    }
+
     /// Remove a node from the graph.
     /// The `dependencies` and `dependents` links of affected nodes should be updated.
     ///
@@ -101,12 +188,14 @@ impl<T> Graph<T> {
             Some(node) => {
                 for dependency in &node.borrow().dependencies {
                     let mut dependency = dependency.borrow_mut();
-                    dependency
-                        .dependents
-                        .retain(|dependent| dependent.borrow().id != id);
+                    dependency.dependents.retain(|dependent| {
+                        dependent
+                            .upgrade()
+                            .is_some_and(|dependent| dependent.borrow().id != id)
+                    });
                 }
 
-                for dependent in &node.borrow().dependents {
+                for dependent in node.borrow().dependents.iter().filter_map(Weak::upgrade) {
                     let mut dependent = dependent.borrow_mut();
                     dependent
                         .dependencies
@@ -117,12 +206,35 @@ impl<T> Graph<T> {
         }
     }
 
+    /// Remove all nodes for which `keep` returns false, updating the links of the remaining nodes.
+    ///
+    /// Returns sorted IDs of unfinished nodes that became ready after this operation.
+    fn retain<F: Fn(NodeId) -> bool>(&mut self, keep: F) -> Vec<NodeId> {
+        let (kept, removed): (Vec<NodeId>, Vec<NodeId>) =
+            self.nodes.keys().copied().partition(|id| keep(*id));
+        let waiting: Vec<NodeId> = kept
+            .into_iter()
+            .filter(|id| !self.is_ready(*id))
+            .collect();
+
+        for id in removed {
+            self.remove(id);
+        }
+
+        let mut ready: Vec<NodeId> = waiting
+            .into_iter()
+            .filter(|id| self.is_ready(*id))
+            .collect();
+        ready.sort_unstable();
+        ready
+    }
+
     /// Finish the node with the given `id` with the provided `value`.
     /// If the given node is not **ready** (or does not exist), the function should panic.
     ///
     /// Returns node IDs of (directly) dependent tasks that are ready after this operation.
     fn finish(&self, id: NodeId, value: T) -> Vec<NodeId> {
-        let Some(node) = self.nodes.get(&id) else {
+        let Some(is_finished) = self.with_node(id, |node| node.value.is_some()) else {
             panic!("Node ID {} does not exist", id);
         };
 
@@ -130,33 +242,71 @@ impl<T> Graph<T> {
             panic!("Node ID {} is not ready", id);
         }
 
-        if node.borrow().value.is_some() {
+        if is_finished {
             panic!("Node ID {} is already finished", id);
         }
 
-        node.borrow_mut().value = Some(value);
+        self.nodes[&id].borrow_mut().value = Some(value);
 
-        let mut ready_dependents = vec![];
-        for dependent in &node.borrow().dependents {
-            if self.is_ready(dependent.borrow().id) {
-                ready_dependents.push(dependent.borrow().id);
-            }
+        let ready_dependents: Vec<NodeId> = self
+            .get_dependents(id)
+            .into_iter()
+            .filter(|dependent| self.is_ready(*dependent))
+            .collect();
+
+        if let Some(on_finish) = self.on_finish.borrow_mut().as_mut() {
+            on_finish(id, &ready_dependents);
         }
 
         ready_dependents
     }
 
+    /// Finishes the node with the given `id` with the value computed by `f` from the values of its
+    /// direct dependencies (in the order of `dependencies`).
+    /// If the given node is not **ready** (or does not exist), the function should panic.
+    ///
+    /// Returns the same node IDs as [`Graph::finish`].
+    fn compute(&self, id: NodeId, f: impl FnOnce(&[T]) -> T) -> Vec<NodeId>
+    where
+        T: Clone,
+    {
+        let Some(values) = self.with_node(id, |node| {
+            node.dependencies
+                .iter()
+                .map(|dependency| dependency.borrow().value.clone())
+                .collect::<Option<Vec<T>>>()
+        }) else {
+            panic!("Node ID {} does not exist", id);
+        };
+        let Some(values) = values else {
+            panic!("Node ID {} is not ready", id);
+        };
+
+        self.finish(id, f(&values))
+    }
+
+    /// Sets a callback that is invoked by every [`Graph::finish`] call with the finished node ID and
+    /// the IDs of dependents that became ready. Replaces any previously set callback.
+    fn set_on_finish(&mut self, cb: FinishCallback) {
+        *self.on_finish.get_mut() = Some(cb);
+    }
+
+    /// Calls `f` with the node with the given `id` borrowed immutably, or returns `None` if the node
+    /// does not exist.
+    /// The borrow only lasts for the duration of `f`, so reads should go through this helper rather
+    /// than borrowing the same node repeatedly within one expression.
+    fn with_node<R>(&self, id: NodeId, f: impl FnOnce(&Node<T>) -> R) -> Option<R> {
+        self.nodes.get(&id).map(|node| f(&node.borrow()))
+    }
+
     /// Returns true if the node with the given `id` is **ready**.
     fn is_ready(&self, id: NodeId) -> bool {
-        self.nodes
-            .get(&id)
-            .map(|node| {
-                node.borrow()
-                    .dependencies
-                    .iter()
-                    .all(|dependency| dependency.borrow().value.is_some())
-            })
-            .unwrap_or(false)
+        self.with_node(id, |node| {
+            node.dependencies
+                .iter()
+                .all(|dependency| dependency.borrow().value.is_some())
+        })
+        .unwrap_or(false)
     }
 
     /// Returns the value within a node with the given `id`.
@@ -164,37 +314,55 @@ impl<T> Graph<T> {
     where
         T: Clone,
     {
-        self.nodes
-            .get(&id)
-            .map(|node| node.borrow().value.clone())?
+        self.with_node(id, |node| node.value.clone())?
     }
 
     /// Returns IDs of the direct dependencies of the node with the given `id`.
     fn get_dependencies(&self, id: NodeId) -> Vec<NodeId> {
-        self.nodes
-            .get(&id)
-            .map(|node| {
-                node.borrow()
-                    .dependencies
-                    .iter()
-                    .map(|dependency| dependency.borrow().id)
-                    .collect()
-            })
-            .unwrap_or_default()
+        self.with_node(id, |node| {
+            node.dependencies
+                .iter()
+                .map(|dependency| dependency.borrow().id)
+                .collect()
+        })
+        .unwrap_or_default()
     }
 
     /// Returns IDs of nodes that directly depend on the node with the given `id`.
     fn get_dependents(&self, id: NodeId) -> Vec<NodeId> {
-        self.nodes
-            .get(&id)
-            .map(|node| {
-                node.borrow()
-                    .dependents
-                    .iter()
-                    .map(|dependent| dependent.borrow().id)
-                    .collect()
-            })
-            .unwrap_or_default()
+        self.with_node(id, |node| {
+            node.dependents
+                .iter()
+                .filter_map(Weak::upgrade)
+                .map(|dependent| dependent.borrow().id)
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    /// Returns IDs of nodes that directly depend on the node with the given `id`, sorted in
+    /// ascending order (unlike [`Graph::get_dependents`], which keeps the order they were added in).
+    fn get_dependents_sorted(&self, id: NodeId) -> Vec<NodeId> {
+        let mut dependents = self.get_dependents(id);
+        dependents.sort_unstable();
+        dependents
+    }
+
+    /// Returns the number of direct dependencies of the node with the given `id`, or 0 if it does
+    /// not exist.
+    /// Same as `get_dependencies(id).len()`, but without collecting the IDs.
+    fn in_degree(&self, id: NodeId) -> usize {
+        self.with_node(id, |node| node.dependencies.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of nodes that directly depend on the node with the given `id`, or 0 if
+    /// it does not exist.
+    /// Same as `get_dependents(id).len()`, but without collecting the IDs. `remove` unlinks the
+    /// removed nodes from their dependencies, so there are no dead links to filter.
+    fn out_degree(&self, id: NodeId) -> usize {
+        self.with_node(id, |node| node.dependents.len())
+            .unwrap_or(0)
     }
 
     /// Returns an iterator over **all** transitive dependencies of the node with the given `id`.
@@ -207,19 +375,40 @@ impl<T> Graph<T> {
     /// trait. Once generators are stabilized, it would also be possible to be implemented directly
     /// within this function :)
     fn dependencies_iter(&self, id: NodeId) -> impl Iterator<Item=NodeId> + '_ {
+        self.dependencies_within(id, usize::MAX)
+    }
+
+    /// Returns an iterator over transitive dependencies of the node with the given `id` that are
+    /// at most `max_depth` hops away (depth 1 are the direct dependencies).
+    /// The order and de-duplication are the same as in [`Graph::dependencies_iter`].
+    fn dependencies_within(
+        &self,
+        id: NodeId,
+        max_depth: usize,
+    ) -> impl Iterator<Item = NodeId> + '_ {
         struct DependenciesIterator<T> {
             visited: HashSet<NodeId>,
-            queue: VecDeque<Rc<RefCell<Node<T>>>>,
+            /// Nodes to visit together with their depth
+            queue: VecDeque<(Rc<RefCell<Node<T>>>, usize)>,
+            max_depth: usize,
         }
 
         impl<T> Iterator for DependenciesIterator<T> {
             type Item = NodeId;
 
             fn next(&mut self) -> Option<Self::Item> {
-                while let Some(node) = self.queue.pop_front() {
+                // Nodes are dequeued in order of their depth, so each node is first reached
+                // through one of its shortest chains
+                while let Some((node, depth)) = self.queue.pop_front() {
                     if self.visited.insert(node.borrow().id) {
-                        self.queue
-                            .extend(node.borrow().dependencies.iter().cloned());
+                        if depth < self.max_depth {
+                            self.queue.extend(
+                                node.borrow()
+                                    .dependencies
+                                    .iter()
+                                    .map(|dependency| (dependency.clone(), depth + 1)),
+                            );
+                        }
                         return Some(node.borrow().id);
                     }
                 }
@@ -229,27 +418,246 @@ impl<T> Graph<T> {
         }
 
         let visited = HashSet::new();
-        let queue = VecDeque::from(
-            self.nodes
-                .get(&id)
-                .map(|node| node.borrow().dependencies.to_vec())
-                .unwrap_or_default(),
-        );
+        let queue = if max_depth == 0 {
+            VecDeque::new()
+        } else {
+            self.with_node(id, |node| {
+                node.dependencies
+                    .iter()
+                    .map(|dependency| (dependency.clone(), 1))
+                    .collect()
+            })
+            .unwrap_or_default()
+        };
+
+        DependenciesIterator {
+            visited,
+            queue,
+            max_depth,
+        }
+    }
+
+    /// Returns true if the node with the given `id` and all of its transitive dependencies are
+    /// **finished**.
+    fn is_finished_recursive(&self, id: NodeId) -> bool {
+        let is_finished = |id: NodeId| {
+            self.with_node(id, |node| node.value.is_some())
+                .unwrap_or(false)
+        };
+        is_finished(id) && self.dependencies_iter(id).all(is_finished)
+    }
+
+    /// Returns a shortest chain of node IDs leading from `from` through its `dependencies` to `to`
+    /// (both inclusive), or `None` if `to` is not a transitive dependency of `from`.
+    fn path(&self, from: NodeId, to: NodeId) -> Option<Vec<NodeId>> {
+        let start = self.nodes.get(&from)?;
+        let mut parents = HashMap::from([(from, from)]);
+        let mut queue = VecDeque::from([start.clone()]);
+
+        while let Some(node) = queue.pop_front() {
+            let id = node.borrow().id;
+            if id == to && id != from {
+                let mut path = vec![id];
+                let mut current = id;
+                while current != from {
+                    current = parents[&current];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for dependency in &node.borrow().dependencies {
+                let dependency_id = dependency.borrow().id;
+                if let Entry::Vacant(entry) = parents.entry(dependency_id) {
+                    entry.insert(id);
+                    queue.push_back(dependency.clone());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns true if every dependency chain from `b` toward the roots (nodes without
+    /// dependencies) passes through `a`. Every node dominates itself.
+    fn dominates(&self, a: NodeId, b: NodeId) -> bool {
+        if !self.nodes.contains_key(&a) {
+            return false;
+        }
+        let Some(start) = self.nodes.get(&b) else {
+            return false;
+        };
+
+        // Search for a root that is reachable from `b` while avoiding `a`
+        let mut visited = HashSet::from([a]);
+        let mut queue = VecDeque::from([start.clone()]);
+        while let Some(node) = queue.pop_front() {
+            let node = node.borrow();
+            if !visited.insert(node.id) {
+                continue;
+            }
+            if node.dependencies.is_empty() {
+                return false;
+            }
+            queue.extend(node.dependencies.iter().cloned());
+        }
+
+        true
+    }
+
+    /// Returns transitive dependencies of the node with the given `id` that can be reached through
+    /// at least two distinct direct dependencies, in breadth-first order.
+    fn shared_dependencies(&self, id: NodeId) -> Vec<NodeId> {
+        let mut counts = HashMap::<NodeId, usize>::new();
+        let direct: HashSet<NodeId> = self.get_dependencies(id).into_iter().collect();
+        for dependency in direct {
+            let reachable = std::iter::once(dependency).chain(self.dependencies_iter(dependency));
+            for reached in reachable {
+                *counts.entry(reached).or_default() += 1;
+            }
+        }
 
-        DependenciesIterator { visited, queue }
+        self.dependencies_iter(id)
+            .filter(|dependency| counts.get(dependency).is_some_and(|count| *count >= 2))
+            .collect()
+    }
+
+    /// Returns the chain of nodes with the largest total `cost`, leading from a root (a node
+    /// without dependencies) to a leaf (a node without dependents), together with its total cost.
+    /// Ties are broken in favour of smaller node IDs (at the end of the chain) and earlier
+    /// dependencies.
+    fn critical_path(&self, cost: impl Fn(NodeId) -> u64) -> (u64, Vec<NodeId>) {
+        /// Computes the cost of the most expensive chain ending in `node`, and remembers the
+        /// dependency that precedes `node` on that chain.
+        fn longest<T>(
+            node: &Rc<RefCell<Node<T>>>,
+            cost: &impl Fn(NodeId) -> u64,
+            memo: &mut HashMap<NodeId, (u64, Option<NodeId>)>,
+        ) -> u64 {
+            let node = node.borrow();
+            if let Some((total, _)) = memo.get(&node.id) {
+                return *total;
+            }
+            let mut best: Option<(u64, NodeId)> = None;
+            for dependency in &node.dependencies {
+                let total = longest(dependency, cost, memo);
+                if best.is_none_or(|(best, _)| total > best) {
+                    best = Some((total, dependency.borrow().id));
+                }
+            }
+            let total = cost(node.id) + best.map_or(0, |(total, _)| total);
+            memo.insert(node.id, (total, best.map(|(_, id)| id)));
+            total
+        }
+
+        let mut ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        ids.sort_unstable();
+
+        let mut memo = HashMap::new();
+        let mut end: Option<(u64, NodeId)> = None;
+        for id in ids {
+            let total = longest(&self.nodes[&id], &cost, &mut memo);
+            if end.is_none_or(|(best, _)| total > best) {
+                end = Some((total, id));
+            }
+        }
+
+        let Some((total, id)) = end else {
+            return (0, vec![]);
+        };
+        let mut path = vec![id];
+        while let Some(previous) = memo[path.last().unwrap()].1 {
+            path.push(previous);
+        }
+        path.reverse();
+        (total, path)
+    }
+
+    /// Returns true if there is no cycle formed by the `dependencies` links of the graph.
+    /// `add` cannot create cycles, so this is only a defensive check of the whole graph.
+    fn is_acyclic(&self) -> bool {
+        /// Returns false if a node that is on the DFS `stack` can be reached from `node`.
+        fn visit<T>(
+            node: &Rc<RefCell<Node<T>>>,
+            stack: &mut HashSet<NodeId>,
+            visited: &mut HashSet<NodeId>,
+        ) -> bool {
+            let node = node.borrow();
+            if visited.contains(&node.id) {
+                return true;
+            }
+            if !stack.insert(node.id) {
+                return false;
+            }
+            if !node
+                .dependencies
+                .iter()
+                .all(|dependency| visit(dependency, stack, visited))
+            {
+                return false;
+            }
+            stack.remove(&node.id);
+            visited.insert(node.id);
+            true
+        }
+
+        let mut stack = HashSet::new();
+        let mut visited = HashSet::new();
+        self.nodes
+            .values()
+            .all(|node| visit(node, &mut stack, &mut visited))
+    }
+
+    /// Returns a new graph with copies of the node `root` and all its transitive dependencies
+    /// (including their values), linked the same way as in this graph.
+    /// Dependents outside of the subgraph are not copied, and the dependents of the copied nodes
+    /// can be in a different order.
+    ///
+    /// If the given node does not exist, the function should panic.
+    fn extract_subgraph(&self, root: NodeId) -> Graph<T>
+    where
+        T: Clone,
+    {
+        if !self.nodes.contains_key(&root) {
+            panic!("Node ID {} does not exist", root);
+        }
+
+        let edges: Vec<(NodeId, Vec<NodeId>)> = std::iter::once(root)
+            .chain(self.dependencies_iter(root))
+            .map(|id| (id, self.get_dependencies(id)))
+            .collect();
+        // The subgraph is closed under dependencies and `add` cannot create cycles
+        let graph = Graph::from_edges(&edges).expect("subgraph cannot be built");
+        for (id, _) in &edges {
+            graph.nodes[id].borrow_mut().value = self.get_value(*id);
+        }
+        graph
     }
 
     /// Return the number of nodes in the graph.
     fn len(&self) -> usize {
         self.nodes.len()
     }
+
+    /// Returns `(finished, total)` counts of nodes in the graph.
+    fn progress(&self) -> (usize, usize) {
+        let finished = self
+            .nodes
+            .values()
+            .filter(|node| node.borrow().value.is_some())
+            .count();
+        (finished, self.len())
+    }
 }
 
 /// Below you can find a set of unit tests.
 #[cfg(test)]
 mod tests {
-    use crate::{Graph, NodeId};
+    use crate::{Graph, GraphError, NodeId};
+    use std::cell::RefCell;
     use std::fmt::Debug;
+    use std::rc::Rc;
 
     #[test]
     fn length() {
@@ -259,6 +667,26 @@ mod tests {
         assert_eq!(graph.len(), 2);
     }
 
+    #[test]
+    fn progress() {
+        let mut graph = Graph::<u32>::default();
+        assert_eq!(graph.progress(), (0, 0));
+
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        assert_eq!(graph.progress(), (0, 3));
+
+        graph.finish(0, 1);
+        assert_eq!(graph.progress(), (1, 3));
+
+        graph.finish(2, 2);
+        assert_eq!(graph.progress(), (2, 3));
+
+        graph.remove(2);
+        assert_eq!(graph.progress(), (1, 2));
+    }
+
     // Shortened macro name, because `insta::assert_compact_debug_snapshot` is quite long.
     macro_rules! check {
         ($($arg:tt)*) => {
@@ -444,6 +872,555 @@ mod tests {
         assert_eq!(deps.collect::<Vec<_>>(), vec![1, 5, 0, 3, 4, 2]);
     }
 
+    #[test]
+    fn dependencies_within_depth() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3, 1, 0]);
+        graph.add(5, vec![3, 4]);
+        graph.add(6, vec![1, 5]);
+
+        let within = |max_depth| graph.dependencies_within(6, max_depth).collect::<Vec<_>>();
+        assert_eq!(within(0), vec![]);
+        assert_eq!(within(1), vec![1, 5]);
+        assert_eq!(within(1), graph.get_dependencies(6));
+        assert_eq!(within(2), vec![1, 5, 0, 3, 4]);
+        assert_eq!(within(3), graph.dependencies_iter(6).collect::<Vec<_>>());
+        assert_eq!(within(10), within(3));
+        assert_eq!(graph.dependencies_within(7, 2).count(), 0);
+    }
+
+    #[test]
+    fn finished_recursive_partial() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.finish(0, 1);
+        graph.finish(1, 2);
+
+        assert!(graph.is_finished_recursive(0));
+        assert!(graph.is_finished_recursive(1));
+        assert!(!graph.is_finished_recursive(2));
+        assert!(!graph.is_finished_recursive(3));
+        assert!(!graph.is_finished_recursive(4));
+    }
+
+    #[test]
+    fn finished_recursive_full() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        assert!(!graph.is_finished_recursive(3));
+
+        graph.finish(0, 1);
+        graph.finish(1, 2);
+        graph.finish(2, 3);
+        assert!(!graph.is_finished_recursive(3));
+
+        graph.finish(3, 4);
+        assert!(graph.is_finished_recursive(3));
+    }
+
+    #[test]
+    fn path_reachable() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3, 1, 0]);
+        graph.add(5, vec![3, 4]);
+        graph.add(6, vec![1, 5]);
+
+        assert_eq!(graph.path(6, 0), Some(vec![6, 1, 0]));
+        assert_eq!(graph.path(6, 2), Some(vec![6, 5, 3, 2]));
+        assert_eq!(graph.path(5, 4), Some(vec![5, 4]));
+    }
+
+    #[test]
+    fn path_unreachable() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3, 1, 0]);
+        graph.add(5, vec![3, 4]);
+        graph.add(6, vec![1, 5]);
+
+        assert_eq!(graph.path(2, 1), None);
+        assert_eq!(graph.path(0, 6), None);
+        assert_eq!(graph.path(3, 3), None);
+        assert_eq!(graph.path(7, 0), None);
+    }
+
+    #[test]
+    fn dominates_diamond() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+
+        assert!(graph.dominates(0, 3));
+        assert!(graph.dominates(0, 1));
+        assert!(graph.dominates(3, 3));
+        assert!(!graph.dominates(1, 3));
+        assert!(!graph.dominates(2, 3));
+        assert!(!graph.dominates(3, 0));
+        assert!(!graph.dominates(1, 2));
+    }
+
+    #[test]
+    fn dominates_bottleneck() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3]);
+        graph.add(5, vec![4]);
+        graph.add(6, vec![5, 1]);
+        graph.add(7, vec![]);
+        graph.add(8, vec![4, 7]);
+
+        assert!(graph.dominates(3, 5));
+        assert!(!graph.dominates(3, 6));
+        assert!(graph.dominates(0, 6));
+        assert!(!graph.dominates(1, 6));
+        assert!(!graph.dominates(4, 8));
+        assert!(!graph.dominates(0, 8));
+        assert!(!graph.dominates(9, 8));
+        assert!(!graph.dominates(0, 9));
+    }
+
+    #[test]
+    fn retain_chain() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![1]);
+        graph.add(3, vec![2]);
+        graph.finish(0, 1);
+
+        assert_eq!(graph.retain(|id| id != 1), vec![2]);
+        assert_eq!(graph.len(), 3);
+        check!(node(&graph, 0), @"NodeStats { dependencies: [], dependents: [], value: Some(1), ready: true }");
+        check!(node(&graph, 2), @"NodeStats { dependencies: [], dependents: [3], value: None, ready: true }");
+        check!(node(&graph, 3), @"NodeStats { dependencies: [2], dependents: [], value: None, ready: false }");
+    }
+
+    #[test]
+    fn retain_multiple() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3, 1, 0]);
+        graph.finish(0, 1);
+        graph.finish(1, 2);
+
+        assert_eq!(graph.retain(|id| id % 2 == 1), vec![3]);
+        assert_eq!(graph.len(), 2);
+        check!(node(&graph, 1), @"NodeStats { dependencies: [], dependents: [3], value: Some(2), ready: true }");
+        check!(node(&graph, 3), @"NodeStats { dependencies: [1], dependents: [], value: None, ready: true }");
+
+        assert_eq!(graph.retain(|_| true), vec![]);
+        assert_eq!(graph.retain(|_| false), vec![]);
+        assert_eq!(graph.len(), 0);
+    }
+
+    #[test]
+    fn shared_dependencies_diamond() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+
+        assert_eq!(graph.shared_dependencies(3), vec![0]);
+        assert_eq!(graph.shared_dependencies(1), vec![]);
+        assert_eq!(graph.shared_dependencies(5), vec![]);
+    }
+
+    #[test]
+    fn shared_dependencies_complex() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3, 1, 0]);
+
+        assert_eq!(graph.shared_dependencies(4), vec![1, 0]);
+    }
+
+    #[test]
+    fn shared_dependencies_tree() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1]);
+        graph.add(4, vec![2, 3]);
+
+        assert_eq!(graph.shared_dependencies(4), vec![]);
+    }
+
+    #[test]
+    fn compute_sum() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![]);
+        graph.add(2, vec![0, 1]);
+        graph.add(3, vec![2, 0]);
+        graph.finish(0, 2);
+        let sum = |values: &[u32]| values.iter().sum();
+        assert_eq!(graph.compute(1, |_| 5), vec![2]);
+        assert_eq!(graph.compute(2, sum), vec![3]);
+        assert_eq!(graph.get_value(2), Some(7));
+        assert_eq!(graph.compute(3, sum), vec![]);
+        assert_eq!(graph.get_value(3), Some(9));
+    }
+
+    #[test]
+    #[should_panic]
+    fn compute_not_ready() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.compute(1, |values| values.iter().sum());
+    }
+
+    #[test]
+    fn on_finish_callback() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+
+        let calls = Rc::new(RefCell::new(vec![]));
+        let recorded = calls.clone();
+        graph.set_on_finish(Box::new(move |id, ready| {
+            let mut ready = ready.to_vec();
+            ready.sort_unstable();
+            recorded.borrow_mut().push((id, ready));
+        }));
+
+        graph.finish(0, 1);
+        graph.finish(2, 2);
+        graph.finish(1, 3);
+        graph.finish(3, 4);
+        assert_eq!(
+            *calls.borrow(),
+            vec![(0, vec![1, 2]), (2, vec![]), (1, vec![3]), (3, vec![])]
+        );
+    }
+
+    #[test]
+    fn finish_many_dependents() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![]);
+        // Even dependents also wait for node 1
+        for id in 2..202 {
+            let dependencies = if id % 2 == 0 { vec![0, 1] } else { vec![0] };
+            graph.add(id, dependencies);
+        }
+
+        let ready = graph.finish(0, 0);
+        assert_eq!(ready, (3..202).step_by(2).collect::<Vec<_>>());
+        let ready = graph.finish(1, 1);
+        assert_eq!(ready, (2..202).step_by(2).collect::<Vec<_>>());
+        for id in 2..202 {
+            assert!(graph.finish(id, id as u32).is_empty());
+        }
+
+        // No borrow of any node outlives the calls above
+        debug_assert!(graph
+            .nodes
+            .values()
+            .all(|node| node.try_borrow_mut().is_ok()));
+        assert_eq!(graph.progress(), (202, 202));
+    }
+
+    #[test]
+    fn nodes_freed_after_drop() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0, 1]);
+
+        let root = graph.nodes[&0].clone();
+        let leaf = Rc::downgrade(&graph.nodes[&2]);
+        // Node 0 is owned by the graph, by the dependencies of nodes 1 and 2 and by `root`
+        assert_eq!(Rc::strong_count(&root), 4);
+        // Dependents are only referenced weakly (by nodes 0 and 1 and by `leaf`)
+        assert_eq!(Rc::strong_count(&graph.nodes[&2]), 1);
+        assert_eq!(Rc::weak_count(&graph.nodes[&2]), 3);
+
+        drop(graph);
+        assert!(leaf.upgrade().is_none());
+        assert_eq!(Rc::strong_count(&root), 1);
+        assert!(root
+            .borrow()
+            .dependents
+            .iter()
+            .all(|dependent| dependent.upgrade().is_none()));
+    }
+
+    #[test]
+    fn critical_path_heavier_branch() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3]);
+
+        let cost = |id: NodeId| match id {
+            2 => 10,
+            _ => 1,
+        };
+        assert_eq!(graph.critical_path(cost), (13, vec![0, 2, 3, 4]));
+        let cost = |id: NodeId| match id {
+            1 => 10,
+            _ => 1,
+        };
+        assert_eq!(graph.critical_path(cost), (13, vec![0, 1, 3, 4]));
+    }
+
+    #[test]
+    fn critical_path_disconnected() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![1]);
+        graph.add(3, vec![]);
+        graph.add(4, vec![3]);
+
+        let cost = |id: NodeId| match id {
+            3 => 100,
+            _ => 5,
+        };
+        assert_eq!(graph.critical_path(cost), (105, vec![3, 4]));
+        assert_eq!(graph.critical_path(|_| 1), (3, vec![0, 1, 2]));
+        assert_eq!(Graph::<u32>::default().critical_path(|_| 1), (0, vec![]));
+    }
+
+    #[test]
+    fn acyclic_dag() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![]);
+        assert!(graph.is_acyclic());
+        assert!(Graph::<u32>::default().is_acyclic());
+    }
+
+    #[test]
+    fn acyclic_manual_cycle() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![1]);
+        add_dependency_unchecked(&graph, 0, 2);
+        assert!(!graph.is_acyclic());
+
+        // Break the cycle, so that the nodes can be freed
+        graph.nodes[&0].borrow_mut().dependencies.clear();
+        assert!(graph.is_acyclic());
+    }
+
+    #[test]
+    fn from_edges_reverse_order() {
+        let graph = Graph::<u32>::from_edges(&[
+            (4, vec![3, 1]),
+            (3, vec![1, 2]),
+            (2, vec![0]),
+            (1, vec![0]),
+            (0, vec![]),
+        ])
+        .unwrap();
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph.get_dependencies(4), vec![3, 1]);
+        assert_eq!(graph.get_dependencies(3), vec![1, 2]);
+        assert_eq!(sorted(graph.get_dependents(0)), vec![1, 2]);
+        assert_eq!(sorted(graph.get_dependents(1)), vec![3, 4]);
+        assert!(graph.is_ready(0));
+        assert!(!graph.is_ready(1));
+
+        assert_eq!(sorted(graph.finish(0, 1)), vec![1, 2]);
+        graph.finish(1, 2);
+        assert_eq!(graph.finish(2, 3), vec![3]);
+        assert_eq!(graph.finish(3, 4), vec![4]);
+    }
+
+    #[test]
+    fn from_edges_errors() {
+        assert_eq!(
+            Graph::<u32>::from_edges(&[(0, vec![]), (1, vec![0]), (0, vec![1])]).err(),
+            Some(GraphError::DuplicateNode(0))
+        );
+        assert_eq!(
+            Graph::<u32>::from_edges(&[(0, vec![]), (1, vec![0, 5])]).err(),
+            Some(GraphError::UnknownDependency {
+                id: 1,
+                dependency: 5
+            })
+        );
+        assert_eq!(
+            Graph::<u32>::from_edges(&[(0, vec![]), (1, vec![2]), (2, vec![3]), (3, vec![1])])
+                .err(),
+            Some(GraphError::Cycle(1))
+        );
+        assert_eq!(
+            Graph::<u32>::from_edges(&[(0, vec![0])]).err(),
+            Some(GraphError::Cycle(0))
+        );
+        assert_eq!(Graph::<u32>::from_edges(&[]).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn dependents_sorted() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        for id in [5, 2, 9, 1, 7] {
+            graph.add(id, vec![0]);
+        }
+        graph.add(3, vec![5]);
+        assert_eq!(graph.get_dependents(0), vec![5, 2, 9, 1, 7]);
+        assert_eq!(graph.get_dependents_sorted(0), vec![1, 2, 5, 7, 9]);
+        assert_eq!(graph.get_dependents_sorted(5), vec![3]);
+        assert!(graph.get_dependents_sorted(3).is_empty());
+        assert!(graph.get_dependents_sorted(42).is_empty());
+
+        graph.remove(2);
+        graph.add(2, vec![0]);
+        assert_eq!(graph.get_dependents(0), vec![5, 9, 1, 7, 2]);
+        assert_eq!(graph.get_dependents_sorted(0), vec![1, 2, 5, 7, 9]);
+    }
+
+    #[test]
+    fn degrees_complex() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![1, 2]);
+        graph.add(4, vec![3, 1, 0]);
+
+        let degrees: Vec<_> = (0..5)
+            .map(|id| (graph.in_degree(id), graph.out_degree(id)))
+            .collect();
+        assert_eq!(degrees, vec![(0, 3), (1, 2), (1, 1), (2, 1), (3, 0)]);
+        for id in 0..5 {
+            assert_eq!(graph.in_degree(id), graph.get_dependencies(id).len());
+            assert_eq!(graph.out_degree(id), graph.get_dependents(id).len());
+        }
+        assert_eq!((graph.in_degree(42), graph.out_degree(42)), (0, 0));
+
+        graph.remove(3);
+        assert_eq!(graph.out_degree(1), 1);
+        assert_eq!(graph.out_degree(2), 0);
+        assert_eq!(graph.in_degree(4), 2);
+    }
+
+    #[test]
+    fn extract_subgraph() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.add(2, vec![0]);
+        graph.add(3, vec![2, 1]);
+        graph.add(4, vec![3]);
+        graph.add(5, vec![]);
+        graph.add(6, vec![1, 5]);
+        graph.finish(0, 10);
+        graph.finish(1, 11);
+
+        let subgraph = graph.extract_subgraph(3);
+        assert_eq!(
+            sorted(subgraph.nodes.keys().copied().collect()),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(subgraph.get_dependencies(3), vec![2, 1]);
+        assert_eq!(subgraph.get_dependencies(2), vec![0]);
+        assert_eq!(subgraph.get_dependencies(1), vec![0]);
+        assert_eq!(subgraph.get_dependents_sorted(0), vec![1, 2]);
+        assert_eq!(subgraph.get_dependents_sorted(1), vec![3]);
+        assert!(subgraph.get_dependents(3).is_empty());
+        assert_eq!(subgraph.get_value(0), Some(10));
+        assert_eq!(subgraph.get_value(1), Some(11));
+        assert_eq!(subgraph.get_value(2), None);
+        assert!(subgraph.is_ready(2));
+
+        // The nodes are independent copies
+        subgraph.finish(2, 12);
+        assert_eq!(graph.get_value(2), None);
+        assert_eq!(graph.len(), 7);
+        assert_eq!(graph.get_dependents_sorted(1), vec![3, 6]);
+
+        assert_eq!(graph.extract_subgraph(5).len(), 1);
+    }
+
+    #[test]
+    fn get_or_add_new() {
+        let mut graph = Graph::<u32>::default();
+        assert!(graph.get_or_add(0, vec![]));
+        assert!(graph.get_or_add(1, vec![0]));
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph.get_dependencies(1), vec![0]);
+        assert_eq!(graph.get_dependents(0), vec![1]);
+    }
+
+    #[test]
+    fn get_or_add_existing() {
+        let mut graph = Graph::<u32>::default();
+        graph.add(0, vec![]);
+        graph.add(1, vec![0]);
+        graph.finish(0, 5);
+
+        // Neither the dependencies nor the value of the existing node change
+        assert!(!graph.get_or_add(1, vec![]));
+        assert!(!graph.get_or_add(0, vec![7]));
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph.get_dependencies(1), vec![0]);
+        assert_eq!(graph.get_dependents(0), vec![1]);
+        assert_eq!(graph.get_value(0), Some(5));
+    }
+
+    #[test]
+    #[should_panic]
+    fn get_or_add_unknown_dependency() {
+        let mut graph = Graph::<u32>::default();
+        graph.get_or_add(0, vec![1]);
+    }
+
+    fn sorted(mut ids: Vec<NodeId>) -> Vec<NodeId> {
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Makes `id` depend on `dependency` without checking that it does not create a cycle.
+    fn add_dependency_unchecked<T>(graph: &Graph<T>, id: NodeId, dependency: NodeId) {
+        let node = &graph.nodes[&id];
+        let dependency = &graph.nodes[&dependency];
+        node.borrow_mut().dependencies.push(dependency.clone());
+        dependency.borrow_mut().dependents.push(Rc::downgrade(node));
+    }
+
     #[derive(Debug)]
     #[allow(unused)]
     struct NodeStats<T> {
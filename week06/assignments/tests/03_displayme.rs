@@ -11,7 +11,7 @@
 /// Below you can find a set of unit tests.
 #[cfg(test)]
 mod tests {
-    use displayme::DisplayMe;
+    use displayme::{DebugMe, DisplayMe};
 
     #[test]
     fn display_unit() {
@@ -58,4 +58,381 @@ mod tests {
             .to_string()
         );
     }
+
+    #[test]
+    fn display_raw_identifiers() {
+        #[derive(DisplayMe)]
+        struct Foo {
+            r#type: u32,
+            r#match: String,
+        }
+        assert_eq!(
+            format!(
+                "{}",
+                Foo {
+                    r#type: 1,
+                    r#match: "bar".to_string()
+                }
+            ),
+            r#"struct Foo {
+    type: 1,
+    match: bar
+}"#
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn debug_unit() {
+        #[derive(DebugMe)]
+        struct Foo;
+        assert_eq!(format!("{:?}", Foo), r#"struct Foo;"#.to_string());
+    }
+
+    #[test]
+    fn debug_tuple_struct() {
+        #[derive(DebugMe)]
+        struct Bar(bool, u32, String);
+        assert_eq!(
+            format!("{:?}", Bar(true, 42, "foo".to_string())),
+            r#"struct Bar (
+    0: true,
+    1: 42,
+    2: "foo"
+)"#
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn debug_named() {
+        #[derive(DebugMe)]
+        struct Foo {
+            a: Option<u32>,
+            b: Vec<char>,
+        }
+        assert_eq!(
+            format!(
+                "{:?}",
+                Foo {
+                    a: Some(5),
+                    b: vec!['x']
+                }
+            ),
+            r#"struct Foo {
+    a: Some(5),
+    b: ['x']
+}"#
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn debug_and_display() {
+        #[derive(DisplayMe, DebugMe)]
+        struct Foo {
+            a: String,
+        }
+        let foo = Foo {
+            a: "bar".to_string(),
+        };
+        assert_eq!(format!("{foo}"), "struct Foo {\n    a: bar\n}");
+        assert_eq!(format!("{foo:?}"), "struct Foo {\n    a: \"bar\"\n}");
+    }
+
+    #[test]
+    fn display_alternate_compact() {
+        #[derive(DisplayMe)]
+        struct Foo {
+            a: u32,
+            b: String,
+        }
+        #[derive(DisplayMe)]
+        struct Bar(bool, u32);
+        #[derive(DisplayMe)]
+        struct Baz;
+
+        let foo = Foo {
+            a: 1,
+            b: "x".to_string(),
+        };
+        assert_eq!(format!("{foo}"), "struct Foo {\n    a: 1,\n    b: x\n}");
+        assert_eq!(format!("{foo:#}"), "struct Foo { a: 1, b: x }");
+        assert_eq!(
+            format!("{:#}", Bar(true, 42)),
+            "struct Bar (0: true, 1: 42)"
+        );
+        assert_eq!(format!("{:#}", Baz), "struct Baz;");
+    }
+
+    #[test]
+    fn display_compact_attribute() {
+        #[derive(DisplayMe)]
+        #[display(compact)]
+        struct Foo {
+            a: u32,
+            b: String,
+        }
+        #[derive(DisplayMe)]
+        #[display(compact)]
+        struct Empty {}
+
+        let foo = Foo {
+            a: 1,
+            b: "x".to_string(),
+        };
+        assert_eq!(format!("{foo}"), "struct Foo { a: 1, b: x }");
+        assert_eq!(format!("{foo:#}"), "struct Foo {\n    a: 1,\n    b: x\n}");
+        assert_eq!(format!("{}", Empty {}), "struct Empty {}");
+    }
+
+    #[test]
+    fn display_debug_field() {
+        #[derive(Debug)]
+        enum Color {
+            Red,
+        }
+        #[derive(DisplayMe)]
+        struct Foo {
+            a: String,
+            #[display(debug)]
+            b: Color,
+            #[display(debug)]
+            c: String,
+        }
+        #[derive(DisplayMe)]
+        #[display(compact)]
+        struct Bar(u32, #[display(debug)] Vec<u32>);
+
+        let foo = Foo {
+            a: "x".to_string(),
+            b: Color::Red,
+            c: "y".to_string(),
+        };
+        assert_eq!(
+            format!("{foo}"),
+            "struct Foo {\n    a: x,\n    b: Red,\n    c: \"y\"\n}"
+        );
+        assert_eq!(format!("{foo:#}"), "struct Foo { a: x, b: Red, c: \"y\" }");
+        assert_eq!(
+            format!("{}", Bar(1, vec![2, 3])),
+            "struct Bar (0: 1, 1: [2, 3])"
+        );
+    }
+
+    #[test]
+    fn display_enum_unit_variant() {
+        #[derive(DisplayMe)]
+        enum Foo {
+            Bar,
+        }
+        assert_eq!(format!("{}", Foo::Bar), "enum Foo::Bar");
+        assert_eq!(format!("{:#}", Foo::Bar), "enum Foo::Bar");
+    }
+
+    #[test]
+    fn display_enum_tuple_variant() {
+        #[derive(DisplayMe)]
+        enum Foo {
+            Bar(bool, String),
+        }
+        let bar = Foo::Bar(true, "x".to_string());
+        assert_eq!(
+            format!("{bar}"),
+            "enum Foo::Bar (\n    0: true,\n    1: x\n)"
+        );
+        assert_eq!(format!("{bar:#}"), "enum Foo::Bar (0: true, 1: x)");
+    }
+
+    #[test]
+    fn display_enum_struct_variant() {
+        #[derive(DisplayMe)]
+        enum Foo {
+            Bar { a: u32, r#type: String },
+            Empty {},
+        }
+        let bar = Foo::Bar {
+            a: 1,
+            r#type: "x".to_string(),
+        };
+        assert_eq!(
+            format!("{bar}"),
+            "enum Foo::Bar {\n    a: 1,\n    type: x\n}"
+        );
+        assert_eq!(format!("{bar:#}"), "enum Foo::Bar { a: 1, type: x }");
+        assert_eq!(format!("{}", Foo::Empty {}), "enum Foo::Empty {}");
+    }
+
+    #[test]
+    fn display_enum_multiple_variants() {
+        #[derive(Debug)]
+        struct Point(i32, i32);
+        #[derive(DisplayMe, DebugMe)]
+        #[display(compact)]
+        enum Shape {
+            Empty,
+            Circle(#[display(debug)] Point, u32),
+            Rectangle { width: u32, height: u32 },
+        }
+        #[derive(DisplayMe)]
+        enum Never {}
+
+        let shapes = [
+            Shape::Empty,
+            Shape::Circle(Point(1, 2), 3),
+            Shape::Rectangle {
+                width: 4,
+                height: 5,
+            },
+        ];
+        let display: Vec<_> = shapes.iter().map(|shape| format!("{shape}")).collect();
+        assert_eq!(
+            display,
+            vec![
+                "enum Shape::Empty",
+                "enum Shape::Circle (0: Point(1, 2), 1: 3)",
+                "enum Shape::Rectangle { width: 4, height: 5 }"
+            ]
+        );
+        assert_eq!(
+            format!("{:?}", shapes[2]),
+            "enum Shape::Rectangle {\n    width: 4,\n    height: 5\n}"
+        );
+    }
+
+    #[test]
+    fn display_skip_named() {
+        #[derive(DisplayMe)]
+        struct First {
+            #[display(skip)]
+            cache: Vec<u32>,
+            a: u32,
+            b: u32,
+        }
+        #[derive(DisplayMe)]
+        struct Middle {
+            a: u32,
+            #[display(skip)]
+            cache: Vec<u32>,
+            b: u32,
+        }
+        #[derive(DisplayMe)]
+        struct Last {
+            a: u32,
+            b: u32,
+            #[display(skip)]
+            marker: std::marker::PhantomData<u8>,
+        }
+        #[derive(DisplayMe)]
+        struct All {
+            #[display(skip)]
+            a: u32,
+        }
+
+        let first = First {
+            cache: vec![1],
+            a: 1,
+            b: 2,
+        };
+        assert_eq!(format!("{first}"), "struct First {\n    a: 1,\n    b: 2\n}");
+        let middle = Middle {
+            a: 1,
+            cache: vec![1],
+            b: 2,
+        };
+        assert_eq!(format!("{middle:#}"), "struct Middle { a: 1, b: 2 }");
+        let last = Last {
+            a: 1,
+            b: 2,
+            marker: std::marker::PhantomData,
+        };
+        assert_eq!(format!("{last}"), "struct Last {\n    a: 1,\n    b: 2\n}");
+        assert_eq!(format!("{last:#}"), "struct Last { a: 1, b: 2 }");
+        assert_eq!(format!("{}", All { a: 1 }), "struct All {}");
+    }
+
+    #[test]
+    fn display_skip_unnamed() {
+        #[derive(DisplayMe)]
+        struct First(#[display(skip)] u32, u32, u32);
+        #[derive(DisplayMe)]
+        struct Middle(u32, #[display(skip)] u32, #[display(debug)] String);
+        #[derive(DisplayMe)]
+        struct Last(u32, u32, #[display(skip)] u32);
+        #[derive(DisplayMe, DebugMe)]
+        enum Foo {
+            Bar(#[display(skip)] u32, u32),
+            Baz {
+                a: u32,
+                #[display(skip)]
+                b: u32,
+            },
+        }
+
+        // The fields keep their positions
+        assert_eq!(
+            format!("{}", First(0, 1, 2)),
+            "struct First (\n    1: 1,\n    2: 2\n)"
+        );
+        assert_eq!(format!("{:#}", First(0, 1, 2)), "struct First (1: 1, 2: 2)");
+        assert_eq!(
+            format!("{:#}", Middle(0, 1, "x".to_string())),
+            "struct Middle (0: 0, 2: \"x\")"
+        );
+        assert_eq!(
+            format!("{}", Last(0, 1, 2)),
+            "struct Last (\n    0: 0,\n    1: 1\n)"
+        );
+        assert_eq!(format!("{:#}", Foo::Bar(0, 1)), "enum Foo::Bar (1: 1)");
+        assert_eq!(
+            format!("{:#}", Foo::Baz { a: 0, b: 1 }),
+            "enum Foo::Baz { a: 0 }"
+        );
+        // The attribute only applies to `DisplayMe`
+        assert_eq!(
+            format!("{:?}", Foo::Baz { a: 0, b: 1 }),
+            "enum Foo::Baz {\n    a: 0,\n    b: 1\n}"
+        );
+    }
+
+    #[test]
+    #[allow(non_upper_case_globals)]
+    fn hygiene() {
+        macro_rules! write {
+            ($($tokens:tt)*) => {
+                compile_error!("not this write!")
+            };
+        }
+        macro_rules! stringify {
+            ($($tokens:tt)*) => {
+                compile_error!("not this stringify!")
+            };
+        }
+
+        mod std {
+            pub mod fmt {
+                pub trait Display {}
+                pub struct Formatter;
+            }
+        }
+
+        struct Formatter;
+        trait Display {
+            fn no_fmt_here(&self);
+        }
+        struct Result<NOPE>(NOPE);
+        struct Ok<NOPE>(NOPE);
+        const f: u8 = 0;
+
+        #[derive(DisplayMe, DebugMe)]
+        struct Foo {
+            f: u32,
+        }
+        #[derive(DisplayMe, DebugMe)]
+        struct Bar(u32);
+
+        assert_eq!(format!("{}", Foo { f: 1 }), "struct Foo {\n    f: 1\n}");
+        assert_eq!(format!("{:?}", Foo { f: 1 }), "struct Foo {\n    f: 1\n}");
+        assert_eq!(format!("{}", Bar(2)), "struct Bar (\n    0: 2\n)");
+    }
 }
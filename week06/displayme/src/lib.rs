@@ -1,99 +1,357 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
+use syn::ext::IdentExt;
 use syn::spanned::Spanned;
 use syn::{parse_macro_input, Data, DeriveInput, Fields};
 
 /// implement the following procedural `#[derive(DisplayMe)]` macro
-/// It should be usable only on structs. When used on enums (or unions), it should produce a compile
+/// It should be usable only on structs and enums. When used on unions, it should produce a compile
 /// error.
 ///
 /// The macro should generate code that will implement the `Display` trait for the struct. The
 /// specific format of the display implementation is defined by tests in the `assignments` crate.
-#[proc_macro_derive(DisplayMe)]
+///
+/// An enum value is displayed as `enum Foo::Variant` followed by the fields of the variant in the
+/// same layout as the fields of a struct. Unit variants are displayed just by their name, without
+/// the `;` of unit structs.
+///
+/// By default, `{}` renders one field per line and the alternate flag (`{:#}`) renders the whole
+/// struct on a single line (`struct Foo { a: 1, b: 2 }`). The container attribute
+/// `#[display(compact)]` swaps the two: `{}` is single-line and `{:#}` is multi-line.
+///
+/// Fields are formatted using their `Display` implementation, fields marked with the attribute
+/// `#[display(debug)]` use their `Debug` implementation instead. Fields marked with
+/// `#[display(skip)]` are left out of the output.
+#[proc_macro_derive(DisplayMe, attributes(display))]
 pub fn derive_display_me(stream: TokenStream) -> TokenStream {
     // Parse the input token stream as an ADT (struct/enum/union) using the `syn` crate
     let input = parse_macro_input!(stream as DeriveInput);
-    match input.data {
-        Data::Struct(syn::DataStruct { fields, .. }) => {
-            let name = &input.ident;
+    let compact = match compact_by_default(&input) {
+        Ok(compact) => compact,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    derive_fmt(
+        input,
+        quote! { ::core::fmt::Display },
+        display_field_format,
+        "DisplayMe",
+        Some(compact),
+    )
+}
 
-            let mut inner_display = quote! { write!(f,"struct {}", stringify!(#name))?;};
+/// Returns the format of a field for `DisplayMe`, which is `{:?}` for fields marked with
+/// `#[display(debug)]` and `{}` otherwise, or `None` for fields marked with `#[display(skip)]`.
+fn display_field_format(field: &syn::Field) -> syn::Result<Option<&'static str>> {
+    let mut format = Some("{}");
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("display"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("debug") {
+                // A skipped field stays skipped
+                format = format.map(|_| "{:?}");
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                format = None;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported display attribute, expected `debug` or `skip`"))
+            }
+        })?;
+    }
+    Ok(format)
+}
 
-            match &fields {
-                Fields::Named(fields) => {
-                    inner_display.extend(quote! {
-                        write!(f, " {{")?;
-                    });
-                    for (i, field) in fields.named.iter().enumerate() {
-                        let identifier = field.ident.as_ref().unwrap();
-                        inner_display.extend(quote! {
-                            write!(f, "\n    {}: {}", stringify!(#identifier), &self.#identifier)?;
-                        });
-                        if i != fields.named.len() - 1 {
-                            inner_display.extend(quote! {
-                                write!(f, ",")?;
-                            });
-                        }
-                    }
-                    if !fields.named.is_empty() {
-                        inner_display.extend(quote! {
-                            write!(f, "\n")?;
-                        });
+/// Returns true if the struct is marked with `#[display(compact)]`.
+fn compact_by_default(input: &DeriveInput) -> syn::Result<bool> {
+    let mut compact = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("display"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("compact") {
+                compact = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported display attribute, expected `compact`"))
+            }
+        })?;
+    }
+    Ok(compact)
+}
+
+/// Companion of `#[derive(DisplayMe)]` that implements the `Debug` trait.
+///
+/// The output has the same layout as `DisplayMe`, but the fields are formatted using their `Debug`
+/// implementation.
+#[proc_macro_derive(DebugMe)]
+pub fn derive_debug_me(stream: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(stream as DeriveInput);
+    derive_fmt(
+        input,
+        quote! { ::core::fmt::Debug },
+        |_| Ok(Some("{:?}")),
+        "DebugMe",
+        None,
+    )
+}
+
+/// Implements the formatting trait `fmt_trait` for a struct or an enum, formatting each field with
+/// the format returned by `field_format` (e.g. `{}` or `{:?}`), fields without a format are
+/// skipped.
+///
+/// With `compact` set, the alternate flag switches between the multi-line and the single-line
+/// layout, and the value says whether the single-line layout is the default one. Without it, the
+/// multi-line layout is always used.
+fn derive_fmt(
+    input: DeriveInput,
+    fmt_trait: TokenStream2,
+    field_format: impl Fn(&syn::Field) -> syn::Result<Option<&'static str>>,
+    macro_name: &str,
+    compact: Option<bool>,
+) -> TokenStream {
+    // Constants in scope would turn a plain `f` binding into a pattern, so use a reserved looking
+    // name with mixed-site hygiene instead
+    let f = syn::Ident::new("__formatter", Span::mixed_site());
+    let body = match fmt_body(&input, &f, &field_format, macro_name, compact) {
+        Ok(body) => body,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    // Generate some tokens that will be appended after the struct
+    let name = &input.ident;
+    let output = quote! {
+        #[automatically_derived]
+        impl #fmt_trait for #name {
+            fn fmt(
+                &self,
+                #f: &mut ::core::fmt::Formatter<'_>,
+            ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                #body
+            }
+        }
+    };
+    output.into()
+}
+
+/// Generates the body of the `fmt` method (including the returned result) for a struct or an
+/// enum, see `derive_fmt`.
+fn fmt_body(
+    input: &DeriveInput,
+    f: &syn::Ident,
+    field_format: &impl Fn(&syn::Field) -> syn::Result<Option<&'static str>>,
+    macro_name: &str,
+    compact: Option<bool>,
+) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    match &input.data {
+        Data::Struct(syn::DataStruct { fields, .. }) => {
+            let values: Vec<_> = fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| match &field.ident {
+                    Some(identifier) => quote! { &self.#identifier },
+                    None => {
+                        let index = syn::Index::from(i);
+                        quote! { &self.#index }
                     }
-                    inner_display.extend(quote! {
-                        write!(f, "}}")?;
-                    });
-                }
-                Fields::Unnamed(fields) => {
-                    inner_display.extend(quote! {
-                        write!(f, " (")?;
+                })
+                .collect();
+            let formats = field_formats(fields, field_format)?;
+            let header = quote! { ::core::write!(#f, "struct {}", ::core::stringify!(#name))?; };
+            let inner_display = layout_fmt(header, fields, &formats, &values, f, compact);
+            Ok(quote! {
+                #inner_display
+                ::core::result::Result::Ok(())
+            })
+        }
+        Data::Enum(syn::DataEnum { variants, .. }) => {
+            let mut arms = TokenStream2::new();
+            for variant in variants {
+                let variant_name = &variant.ident;
+                let formats = field_formats(&variant.fields, field_format)?;
+                let values: Vec<_> = (0..variant.fields.len())
+                    .map(|i| {
+                        let binding = syn::Ident::new(&format!("__field{i}"), Span::mixed_site());
+                        quote! { #binding }
+                    })
+                    .collect();
+                // Skipped fields are not bound, so that the bindings are not unused
+                let bindings = formats
+                    .iter()
+                    .zip(&values)
+                    .map(|(format, value)| match format {
+                        Some(_) => quote! { ref #value },
+                        None => quote! { _ },
                     });
-                    for (i, _) in fields.unnamed.iter().enumerate() {
-                        let identifier = syn::Index::from(i);
-                        inner_display.extend(quote! {
-                            write!(f, "\n    {}: {}", stringify!(#identifier), &self.#identifier)?;
-                        });
-                        if i != fields.unnamed.len() - 1 {
-                            inner_display.extend(quote! {
-                                write!(f, ",")?;
-                            });
-                        }
+                let pattern = match &variant.fields {
+                    Fields::Named(fields) => {
+                        let names = fields.named.iter().map(|field| &field.ident);
+                        quote! { Self::#variant_name { #(#names: #bindings),* } }
                     }
-                    if !fields.unnamed.is_empty() {
-                        inner_display.extend(quote! {
-                            write!(f, "\n")?;
-                        });
+                    Fields::Unnamed(_) => quote! { Self::#variant_name(#(#bindings),*) },
+                    Fields::Unit => quote! { Self::#variant_name },
+                };
+                let header = quote! {
+                    ::core::write!(
+                        #f,
+                        "enum {}::{}",
+                        ::core::stringify!(#name),
+                        ::core::stringify!(#variant_name)
+                    )?;
+                };
+                let inner_display = match &variant.fields {
+                    Fields::Unit => header,
+                    fields => layout_fmt(header, fields, &formats, &values, f, compact),
+                };
+                arms.extend(quote! {
+                    #pattern => {
+                        #inner_display
+                        ::core::result::Result::Ok(())
                     }
+                });
+            }
+            // Dereferencing `self` allows an enum without variants to be matched exhaustively, the
+            // result is returned from each arm, so that there is no unreachable code after it
+            Ok(quote! { match *self { #arms } })
+        }
+        Data::Union(_) => Err(syn::Error::new(
+            input.span(),
+            format!("{macro_name} can only be used on structs and enums"),
+        )),
+    }
+}
+
+/// Returns the formats of all `fields` given by `field_format`.
+fn field_formats(
+    fields: &Fields,
+    field_format: &impl Fn(&syn::Field) -> syn::Result<Option<&'static str>>,
+) -> syn::Result<Vec<Option<&'static str>>> {
+    fields.iter().map(field_format).collect()
+}
+
+/// Generates code that writes the `header` (e.g. the name of a struct) followed by the `fields`
+/// with their `formats`, whose values are references given by the expressions `values`.
+/// The layout is selected according to `compact`, see `derive_fmt`.
+fn layout_fmt(
+    header: TokenStream2,
+    fields: &Fields,
+    formats: &[Option<&str>],
+    values: &[TokenStream2],
+    f: &syn::Ident,
+    compact: Option<bool>,
+) -> TokenStream2 {
+    let mut inner_display = header;
+    let multi_line = fields_fmt(fields, formats, values, f, false);
+    inner_display.extend(match compact {
+        Some(compact) => {
+            let single_line = fields_fmt(fields, formats, values, f, true);
+            quote! {
+                if #f.alternate() != #compact {
+                    #single_line
+                } else {
+                    #multi_line
+                }
+            }
+        }
+        None => multi_line,
+    });
+    inner_display
+}
+
+/// Generates code that writes all `fields` into the formatter `f` (each with its format from
+/// `formats` and its value from `values`), either one field per line or all of them on a single
+/// line (`compact`). Fields without a format are skipped.
+fn fields_fmt(
+    fields: &Fields,
+    formats: &[Option<&str>],
+    values: &[TokenStream2],
+    f: &syn::Ident,
+    compact: bool,
+) -> TokenStream2 {
+    let mut inner_display = TokenStream2::new();
+    // Indices of the displayed fields together with their formats
+    let displayed: Vec<_> = formats
+        .iter()
+        .enumerate()
+        .filter_map(|(i, format)| format.map(|format| (i, format)))
+        .collect();
+
+    match fields {
+        Fields::Named(fields) => {
+            inner_display.extend(quote! {
+                ::core::write!(#f, " {{")?;
+            });
+            for (position, &(i, field_format)) in displayed.iter().enumerate() {
+                let line_format = match compact {
+                    true => format!(" {{}}: {field_format}"),
+                    false => format!("\n    {{}}: {field_format}"),
+                };
+                let identifier = fields.named[i].ident.as_ref().unwrap();
+                // Raw identifiers (e.g. `r#type`) are printed without the `r#` prefix
+                let label = identifier.unraw().to_string();
+                let value = &values[i];
+                inner_display.extend(quote! {
+                    ::core::write!(#f, #line_format, #label, #value)?;
+                });
+                if position != displayed.len() - 1 {
                     inner_display.extend(quote! {
-                        write!(f, ")")?;
+                        ::core::write!(#f, ",")?;
                     });
                 }
-                Fields::Unit => {
+            }
+            if !displayed.is_empty() {
+                let end = if compact { " " } else { "\n" };
+                inner_display.extend(quote! {
+                    ::core::write!(#f, #end)?;
+                });
+            }
+            inner_display.extend(quote! {
+                ::core::write!(#f, "}}")?;
+            });
+        }
+        Fields::Unnamed(_) => {
+            inner_display.extend(quote! {
+                ::core::write!(#f, " (")?;
+            });
+            for (position, &(i, field_format)) in displayed.iter().enumerate() {
+                let identifier = syn::Index::from(i);
+                let line_format = match (compact, position) {
+                    (true, 0) => format!("{{}}: {field_format}"),
+                    (true, _) => format!(" {{}}: {field_format}"),
+                    (false, _) => format!("\n    {{}}: {field_format}"),
+                };
+                let value = &values[i];
+                inner_display.extend(quote! {
+                    ::core::write!(#f, #line_format, ::core::stringify!(#identifier), #value)?;
+                });
+                if position != displayed.len() - 1 {
                     inner_display.extend(quote! {
-                        write!(f, ";")?;
+                        ::core::write!(#f, ",")?;
                     });
                 }
-            };
-
-            // Generate some tokens that will be appended after the struct
-            let output = quote! {
-                impl ::std::fmt::Display for #name {
-                    fn fmt(
-                        &self,
-                        f: &mut ::std::fmt::Formatter<'_>,
-                    ) -> ::std::result::Result<(), ::std::fmt::Error> {
-                        #inner_display
-                        Ok(())
-                    }
-                }
-            };
-            output.into()
+            }
+            if !displayed.is_empty() && !compact {
+                inner_display.extend(quote! {
+                    ::core::write!(#f, "\n")?;
+                });
+            }
+            inner_display.extend(quote! {
+                ::core::write!(#f, ")")?;
+            });
         }
-        Data::Enum(_) | Data::Union(_) => {
-            syn::Error::new(input.span(), "DisplayMe can only be used on structs")
-                .to_compile_error()
-                .into()
+        Fields::Unit => {
+            inner_display.extend(quote! {
+                ::core::write!(#f, ";")?;
+            });
         }
-    }
+    };
+
+    inner_display
 }
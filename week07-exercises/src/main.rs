@@ -1,24 +1,754 @@
-fn main() {
-    // TODO #1: implement a simple version of grep
-    // Your program should go through a specified directory recursively, read the contents of all
-    // files and print all lines (+ their locations) that contain a specified substring.
-    // You don't have to use regexes, a normal substring search will work just fine.
-    // You can use a crate to iterate directories (e.g. `walkdir`) if you want, or just code the
-    // traversal by hand.
-    // You can download e.g. the cargo repository (`git clone https://github.com/rust-lang/cargo)
-    // to have some data to search through, and grep e.g. for Rust keywords in it.
-
-    // TODO #2: add a command-line interface
-    // Use the `clap` crate to add a simple CLI to your program, which will be used to select which
-    // directory (or file) should be searched, and what substring should be searched.
-
-    // TODO #3: add JSON output
-    // Use the `serde` and `serde_json` crates to print the output in JSON, so that it can be
-    // handled programmatically.
-    // Use the CLI to select if the program should print the output in human-readable form or in
-    // JSON.
-
-    // TODO #4: parallelize the search
-    // Perform search across files in parallel.
-    // Perform search across lines/parts of files in parallel.
+// TODO #1: implement a simple version of grep
+// Your program should go through a specified directory recursively, read the contents of all
+// files and print all lines (+ their locations) that contain a specified substring.
+// You don't have to use regexes, a normal substring search will work just fine.
+// You can use a crate to iterate directories (e.g. `walkdir`) if you want, or just code the
+// traversal by hand.
+// You can download e.g. the cargo repository (`git clone https://github.com/rust-lang/cargo)
+// to have some data to search through, and grep e.g. for Rust keywords in it.
+
+// TODO #2: add a command-line interface
+// Use the `clap` crate to add a simple CLI to your program, which will be used to select which
+// directory (or file) should be searched, and what substring should be searched.
+
+// TODO #3: add JSON output
+// Use the `serde` and `serde_json` crates to print the output in JSON, so that it can be
+// handled programmatically.
+// Use the CLI to select if the program should print the output in human-readable form or in
+// JSON.
+
+// TODO #4: parallelize the search
+// Perform search across files in parallel.
+// Perform search across lines/parts of files in parallel.
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::io::{IsTerminal, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Size of the chunk at the beginning of a file that is checked for NUL bytes.
+const BINARY_CHECK_SIZE: usize = 8 * 1024;
+
+/// ANSI escape sequences that start and end the highlighting of a match.
+const COLOR_START: &str = "\x1b[1;31m";
+const COLOR_END: &str = "\x1b[0m";
+
+/// When matched substrings should be highlighted.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ColorChoice {
+    /// Only when the standard output is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+/// Recursively search files for lines containing a substring
+#[derive(Parser, Debug)]
+struct Args {
+    /// Substring to search for
+    pattern: String,
+    /// Directory (or file) to search
+    #[arg(default_value = ".")]
+    path: PathBuf,
+    /// Skip files larger than the given number of bytes
+    #[arg(long, value_name = "BYTES")]
+    max_filesize: Option<u64>,
+    /// Search binary files (files containing a NUL byte) as if they were text
+    #[arg(long)]
+    text: bool,
+    /// Follow symbolic links (each directory is searched only once)
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// Report skipped files on stderr (`-v` is taken by `--invert-match`, as in grep)
+    #[arg(long)]
+    verbose: bool,
+    /// Print lines that do not contain the pattern
+    #[arg(short = 'v', long)]
+    invert_match: bool,
+    /// Match the pattern only when it is bounded by non-word characters
+    #[arg(short = 'w', long)]
+    word_regexp: bool,
+    /// Ignore (ASCII) case distinctions in the pattern and the searched lines
+    #[arg(short, long)]
+    ignore_case: bool,
+    /// Highlight the matched substrings
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Replace the matched substrings with the given text and rewrite the files in place
+    #[arg(long, value_name = "TEXT", conflicts_with = "invert_match")]
+    replace: Option<String>,
+    /// Print the changes made by `--replace` without writing them
+    #[arg(long, requires = "replace")]
+    dry_run: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct Match {
+    path: PathBuf,
+    line: usize,
+    content: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum SkipReason {
+    TooLarge(u64),
+    Binary,
+    AlreadyVisited,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::TooLarge(size) => write!(f, "file too large ({size} bytes)"),
+            SkipReason::Binary => write!(f, "binary file"),
+            SkipReason::AlreadyVisited => write!(f, "directory already visited"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SearchResult {
+    matches: Vec<Match>,
+    skipped: Vec<(PathBuf, SkipReason)>,
+    /// Entries that could not be read, they do not stop the search
+    errors: Vec<(PathBuf, String)>,
+}
+
+/// Changes made to a single file by `--replace` (or only reported, with `--dry-run`).
+#[derive(Debug, PartialEq)]
+struct FileReplacement {
+    path: PathBuf,
+    /// Number of replaced occurrences of the pattern
+    replacements: usize,
+    /// Numbers of the changed lines together with their original and new content
+    lines: Vec<(usize, String, String)>,
+}
+
+/// Characters that form words for `--word-regexp`.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the byte index of the first occurrence of `needle` in `haystack`, ignoring ASCII case
+/// (like [`str::eq_ignore_ascii_case`]), or `None` if there is no such occurrence.
+/// Neither string is copied, the bytes are compared in place.
+fn contains_case_insensitive(haystack: &str, needle: &str) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Returns the byte index of the first occurrence of `pattern` in `line`.
+/// A match always has the same length as `pattern`, since only ASCII case is ignored.
+fn find(line: &str, pattern: &str, ignore_case: bool) -> Option<usize> {
+    if ignore_case {
+        contains_case_insensitive(line, pattern)
+    } else {
+        line.find(pattern)
+    }
+}
+
+/// Returns true if `line` contains `pattern` surrounded by non-word characters (or line bounds).
+fn contains_word(line: &str, pattern: &str, ignore_case: bool) -> bool {
+    let mut offset = 0;
+    while let Some(start) = find(&line[offset..], pattern, ignore_case).map(|start| start + offset)
+    {
+        let before = line[..start].chars().next_back();
+        let after = line[start + pattern.len()..].chars().next();
+        if !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char) {
+            return true;
+        }
+        // Continue right after the first character of this occurrence
+        match line[start..].chars().next() {
+            Some(c) => offset = start + c.len_utf8(),
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Returns true if the `line` should be reported according to the search options.
+fn is_match(line: &str, args: &Args) -> bool {
+    let found = if args.word_regexp {
+        contains_word(line, &args.pattern, args.ignore_case)
+    } else {
+        find(line, &args.pattern, args.ignore_case).is_some()
+    };
+    found != args.invert_match
+}
+
+/// Returns the byte ranges of non-overlapping occurrences of the pattern in `line` that satisfy
+/// the search options (e.g. `--word-regexp`).
+fn match_ranges(line: &str, args: &Args) -> Vec<Range<usize>> {
+    let pattern = &args.pattern;
+    let mut ranges = vec![];
+    if pattern.is_empty() {
+        return ranges;
+    }
+    let mut offset = 0;
+    while let Some(start) =
+        find(&line[offset..], pattern, args.ignore_case).map(|start| start + offset)
+    {
+        let end = start + pattern.len();
+        let before = line[..start].chars().next_back();
+        let after = line[end..].chars().next();
+        if !args.word_regexp
+            || (!before.is_some_and(is_word_char) && !after.is_some_and(is_word_char))
+        {
+            ranges.push(start..end);
+            offset = end;
+        } else {
+            // Continue right after the first character of this occurrence
+            offset = start + line[start..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    ranges
+}
+
+/// Wraps the matched substrings of `line` in ANSI color codes.
+/// Lines printed because of `--invert-match` contain no matches, so they are returned unchanged.
+fn highlight(line: &str, args: &Args) -> String {
+    if args.invert_match {
+        return line.to_string();
+    }
+    let mut output = String::with_capacity(line.len());
+    let mut last = 0;
+    for range in match_ranges(line, args) {
+        output.push_str(&line[last..range.start]);
+        output.push_str(COLOR_START);
+        output.push_str(&line[range.clone()]);
+        output.push_str(COLOR_END);
+        last = range.end;
+    }
+    output.push_str(&line[last..]);
+    output
+}
+
+/// Replaces the matched substrings of `line` with `text`.
+/// Returns the new line and the number of replaced occurrences.
+fn replace_line(line: &str, args: &Args, text: &str) -> (String, usize) {
+    let ranges = match_ranges(line, args);
+    let mut output = String::with_capacity(line.len());
+    let mut last = 0;
+    for range in &ranges {
+        output.push_str(&line[last..range.start]);
+        output.push_str(text);
+        last = range.end;
+    }
+    output.push_str(&line[last..]);
+    (output, ranges.len())
+}
+
+/// Replaces the matched substrings in all lines of the file at `path` with `text`.
+/// Unless `dry_run` is set, the modified file is written to a temporary file next to it, which
+/// then atomically replaces the original.
+/// Line endings are kept as they are. Files that are not valid UTF-8 are not modified, since
+/// they could not be written back unchanged.
+fn replace_in_file(
+    path: &Path,
+    args: &Args,
+    text: &str,
+    dry_run: bool,
+) -> anyhow::Result<FileReplacement> {
+    let content = std::fs::read(path)?;
+    let content = String::from_utf8(content)
+        .with_context(|| format!("{} is not valid UTF-8", path.display()))?;
+
+    let mut replacement = FileReplacement {
+        path: path.to_path_buf(),
+        replacements: 0,
+        lines: vec![],
+    };
+    let mut output = String::with_capacity(content.len());
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        // Same line boundaries as `str::lines`, which is used by the search
+        let ending = if line.ends_with("\r\n") {
+            "\r\n"
+        } else if line.ends_with('\n') {
+            "\n"
+        } else {
+            ""
+        };
+        let line = &line[..line.len() - ending.len()];
+        let (new_line, count) = replace_line(line, args, text);
+        if count > 0 {
+            replacement.replacements += count;
+            replacement
+                .lines
+                .push((index + 1, line.to_string(), new_line.clone()));
+        }
+        output.push_str(&new_line);
+        output.push_str(ending);
+    }
+
+    if !dry_run && replacement.replacements > 0 {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temporary = path.with_file_name(format!(".{file_name}.grep-replace"));
+        std::fs::write(&temporary, output)?;
+        std::fs::set_permissions(&temporary, std::fs::metadata(path)?.permissions())?;
+        std::fs::rename(&temporary, path)
+            .with_context(|| format!("Cannot replace {}", path.display()))?;
+    }
+    Ok(replacement)
+}
+
+/// Resolves `--color` to whether the output should be highlighted.
+fn use_color(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Auto => std::io::stdout().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    }
+}
+
+/// Reads the file at `path`, unless it is binary (contains a NUL byte in its first
+/// [`BINARY_CHECK_SIZE`] bytes), in which case `None` is returned without reading the rest.
+/// With `text`, binary files are read as well.
+fn read_text(path: &Path, text: bool) -> std::io::Result<Option<Vec<u8>>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut content = vec![];
+    file.by_ref()
+        .take(BINARY_CHECK_SIZE as u64)
+        .read_to_end(&mut content)?;
+    if !text && content.contains(&0) {
+        return Ok(None);
+    }
+    file.read_to_end(&mut content)?;
+    Ok(Some(content))
+}
+
+fn search(args: &Args) -> anyhow::Result<SearchResult> {
+    let mut result = SearchResult::default();
+
+    // Canonical paths of searched directories, so that symlink cycles are not followed forever
+    let mut visited = HashSet::new();
+    let mut walker = WalkDir::new(&args.path)
+        .follow_links(args.follow_symlinks)
+        .into_iter();
+
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) if error.loop_ancestor().is_some() => {
+                if let Some(path) = error.path() {
+                    result
+                        .skipped
+                        .push((path.to_path_buf(), SkipReason::AlreadyVisited));
+                }
+                continue;
+            }
+            Err(error) => {
+                let path = error.path().unwrap_or(&args.path).to_path_buf();
+                result.errors.push((path, error.to_string()));
+                continue;
+            }
+        };
+
+        if entry.file_type().is_dir() {
+            if args.follow_symlinks {
+                match entry.path().canonicalize().map(|path| visited.insert(path)) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        result
+                            .skipped
+                            .push((entry.path().to_path_buf(), SkipReason::AlreadyVisited));
+                        walker.skip_current_dir();
+                    }
+                    Err(error) => {
+                        result
+                            .errors
+                            .push((entry.path().to_path_buf(), error.to_string()));
+                        walker.skip_current_dir();
+                    }
+                }
+            }
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        let size = match entry.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(error) => {
+                result.errors.push((path.to_path_buf(), error.to_string()));
+                continue;
+            }
+        };
+        if args.max_filesize.is_some_and(|max| size > max) {
+            result
+                .skipped
+                .push((path.to_path_buf(), SkipReason::TooLarge(size)));
+            continue;
+        }
+
+        let content = match read_text(path, args.text) {
+            Ok(Some(content)) => content,
+            Ok(None) => {
+                result
+                    .skipped
+                    .push((path.to_path_buf(), SkipReason::Binary));
+                continue;
+            }
+            Err(error) => {
+                result.errors.push((path.to_path_buf(), error.to_string()));
+                continue;
+            }
+        };
+
+        let content = String::from_utf8_lossy(&content);
+        for (index, line) in content.lines().enumerate() {
+            if is_match(line, args) {
+                result.matches.push(Match {
+                    path: path.to_path_buf(),
+                    line: index + 1,
+                    content: line.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let result = search(&args)?;
+    let color = use_color(args.color);
+    for (path, error) in &result.errors {
+        eprintln!("Cannot read {}: {error}", path.display());
+    }
+
+    if let Some(text) = &args.replace {
+        let mut paths: Vec<&Path> = result.matches.iter().map(|m| m.path.as_path()).collect();
+        paths.dedup();
+        for path in paths {
+            match replace_in_file(path, &args, text, args.dry_run) {
+                Ok(replacement) => {
+                    if args.dry_run {
+                        for (line, old, new) in &replacement.lines {
+                            println!("{}:{line}: -{old}", path.display());
+                            println!("{}:{line}: +{new}", path.display());
+                        }
+                    }
+                    println!(
+                        "{}: {} replacements",
+                        path.display(),
+                        replacement.replacements
+                    );
+                }
+                Err(error) => eprintln!("Cannot replace in {}: {error:#}", path.display()),
+            }
+        }
+    } else {
+        for Match {
+            path,
+            line,
+            content,
+        } in result.matches
+        {
+            if color {
+                println!("{}:{line}: {}", path.display(), highlight(&content, &args));
+            } else {
+                println!("{}:{line}: {content}", path.display());
+            }
+        }
+    }
+
+    if args.verbose {
+        for (path, reason) in result.skipped {
+            eprintln!("Skipped {}: {reason}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contains_case_insensitive, highlight, replace_in_file, search, use_color, Args, SkipReason,
+        BINARY_CHECK_SIZE,
+    };
+    use clap::Parser;
+    use std::path::{Path, PathBuf};
+
+    /// Creates an empty directory for a test, removing leftovers from previous runs
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("grep-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn args(dir: &Path, extra: &[&str]) -> Args {
+        let mut args = vec!["grep", "needle", dir.to_str().unwrap()];
+        args.extend_from_slice(extra);
+        Args::parse_from(args)
+    }
+
+    #[test]
+    fn find_lines() {
+        let dir = test_dir("find-lines");
+        std::fs::write(dir.join("a.txt"), "hay\nneedle\nhay needle hay\n").unwrap();
+
+        let result = search(&args(&dir, &[])).unwrap();
+        let lines: Vec<_> = result.matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![2, 3]);
+        assert!(result.skipped.is_empty());
+    }
+
+    #[test]
+    fn invert_match() {
+        let dir = test_dir("invert-match");
+        std::fs::write(dir.join("a.txt"), "hay\nneedle\nhay needle hay\nstraw\n").unwrap();
+
+        let result = search(&args(&dir, &["-v"])).unwrap();
+        let lines: Vec<_> = result.matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![1, 4]);
+    }
+
+    #[test]
+    fn word_regexp() {
+        let dir = test_dir("word-regexp");
+        std::fs::write(
+            dir.join("a.txt"),
+            "needles\nneedle_case\nthe needle.\nneedle\nneedleneedle needle\n",
+        )
+        .unwrap();
+
+        let result = search(&args(&dir, &["-w"])).unwrap();
+        let lines: Vec<_> = result.matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+
+        let result = search(&args(&dir, &["--word-regexp", "--invert-match"])).unwrap();
+        let lines: Vec<_> = result.matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![1, 2]);
+    }
+
+    #[test]
+    fn case_insensitive_positions() {
+        assert_eq!(
+            contains_case_insensitive("Needle in hay", "needle"),
+            Some(0)
+        );
+        assert_eq!(
+            contains_case_insensitive("hay NeEdLe hay", "needle"),
+            Some(4)
+        );
+        assert_eq!(contains_case_insensitive("hay needle", "NEEDLE"), Some(4));
+        assert_eq!(
+            contains_case_insensitive("nee NEEDLE needle", "needle"),
+            Some(4)
+        );
+        assert_eq!(contains_case_insensitive("žluť needle", "Needle"), Some(7));
+        assert_eq!(contains_case_insensitive("hay", ""), Some(0));
+        assert_eq!(contains_case_insensitive("", ""), Some(0));
+    }
+
+    #[test]
+    fn case_insensitive_no_match() {
+        assert_eq!(contains_case_insensitive("hay", "needle"), None);
+        assert_eq!(contains_case_insensitive("", "needle"), None);
+        assert_eq!(contains_case_insensitive("needl", "needle"), None);
+        assert_eq!(contains_case_insensitive("ŽLUŤ", "žluť"), None);
+    }
+
+    #[test]
+    fn ignore_case() {
+        let dir = test_dir("ignore-case");
+        std::fs::write(dir.join("a.txt"), "NEEDLE\nneedles\nhay\nA Needle.\n").unwrap();
+
+        let result = search(&args(&dir, &["-i"])).unwrap();
+        let lines: Vec<_> = result.matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![1, 2, 4]);
+
+        let result = search(&args(&dir, &["-i", "-w"])).unwrap();
+        let lines: Vec<_> = result.matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![1, 4]);
+    }
+
+    #[test]
+    fn color_always() {
+        let dir = test_dir("color");
+        let always = args(&dir, &["--color", "always"]);
+        assert!(use_color(always.color));
+        assert_eq!(
+            highlight("hay needle hay needle", &always),
+            "hay \x1b[1;31mneedle\x1b[0m hay \x1b[1;31mneedle\x1b[0m"
+        );
+        assert_eq!(highlight("hay", &always), "hay");
+
+        let words = args(&dir, &["--color", "always", "-w", "-i"]);
+        assert_eq!(
+            highlight("needles NEEDLE", &words),
+            "needles \x1b[1;31mNEEDLE\x1b[0m"
+        );
+
+        assert!(!use_color(args(&dir, &["--color", "never"]).color));
+    }
+
+    #[test]
+    fn replace_literal() {
+        let dir = test_dir("replace-literal");
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "hay\r\nneedle hay needle\nhay\nneedles").unwrap();
+        std::fs::write(dir.join("b.txt"), "hay\n").unwrap();
+
+        let args = args(&dir, &["--replace", "pin"]);
+        let result = search(&args).unwrap();
+        assert_eq!(result.matches.len(), 2);
+        let replacement = replace_in_file(&file, &args, "pin", false).unwrap();
+        assert_eq!(replacement.replacements, 3);
+        assert_eq!(
+            replacement.lines,
+            vec![
+                (
+                    2,
+                    "needle hay needle".to_string(),
+                    "pin hay pin".to_string()
+                ),
+                (4, "needles".to_string(), "pins".to_string())
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "hay\r\npin hay pin\nhay\npins"
+        );
+        // No temporary file is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn replace_words_ignore_case() {
+        let dir = test_dir("replace-words");
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "Needle needles NEEDLE\n").unwrap();
+
+        let args = args(&dir, &["-w", "-i", "--replace", "pin"]);
+        let replacement = replace_in_file(&file, &args, "pin", false).unwrap();
+        assert_eq!(replacement.replacements, 2);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "pin needles pin\n");
+
+        assert!(Args::try_parse_from(["grep", "needle", "-v", "--replace", "pin"]).is_err());
+    }
+
+    #[test]
+    fn replace_dry_run() {
+        let dir = test_dir("replace-dry-run");
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "needle\nhay\n").unwrap();
+
+        let args = args(&dir, &["--replace", "pin", "--dry-run"]);
+        assert!(args.dry_run);
+        let replacement = replace_in_file(&file, &args, "pin", args.dry_run).unwrap();
+        assert_eq!(
+            replacement.lines,
+            vec![(1, "needle".to_string(), "pin".to_string())]
+        );
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "needle\nhay\n");
+
+        assert!(Args::try_parse_from(["grep", "needle", "--dry-run"]).is_err());
+    }
+
+    #[test]
+    fn skip_binary_file() {
+        let dir = test_dir("binary");
+        let file = dir.join("data.bin");
+        std::fs::write(&file, b"\x00\x01needle\x02").unwrap();
+
+        let result = search(&args(&dir, &[])).unwrap();
+        assert!(result.matches.is_empty());
+        assert_eq!(result.skipped, vec![(file, SkipReason::Binary)]);
+
+        let result = search(&args(&dir, &["--text"])).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert!(result.skipped.is_empty());
+    }
+
+    #[test]
+    fn binary_check_size() {
+        let dir = test_dir("binary-check-size");
+        // The NUL byte after the checked chunk does not make the file binary
+        let mut late_nul = "hay\n".repeat(BINARY_CHECK_SIZE / 4).into_bytes();
+        late_nul.extend_from_slice(b"\x00\nneedle\n");
+        std::fs::write(dir.join("late.txt"), late_nul).unwrap();
+        let mut early_nul = b"\x00\n".to_vec();
+        early_nul.extend_from_slice("needle\n".repeat(BINARY_CHECK_SIZE).as_bytes());
+        std::fs::write(dir.join("early.bin"), early_nul).unwrap();
+
+        let result = search(&args(&dir, &[])).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].line, BINARY_CHECK_SIZE / 4 + 2);
+        assert_eq!(
+            result.skipped,
+            vec![(dir.join("early.bin"), SkipReason::Binary)]
+        );
+    }
+
+    #[test]
+    fn unreadable_entries() {
+        let dir = test_dir("unreadable");
+        std::fs::write(dir.join("file.txt"), "needle\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken")).unwrap();
+
+        // The broken link is reported, but does not stop the search
+        let result = search(&args(&dir, &["--follow-symlinks"])).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].0, dir.join("broken"));
+    }
+
+    #[test]
+    fn skip_large_file() {
+        let dir = test_dir("large");
+        let large = dir.join("large.txt");
+        std::fs::write(&large, "needle\n".repeat(100)).unwrap();
+        std::fs::write(dir.join("small.txt"), "needle\n").unwrap();
+
+        let result = search(&args(&dir, &["--max-filesize", "100"])).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.skipped, vec![(large, SkipReason::TooLarge(700))]);
+
+        let result = search(&args(&dir, &[])).unwrap();
+        assert_eq!(result.matches.len(), 101);
+    }
+
+    #[test]
+    fn follow_symlinks() {
+        let dir = test_dir("symlinks");
+        let nested = dir.join("a");
+        std::fs::create_dir(&nested).unwrap();
+        std::fs::write(nested.join("file.txt"), "needle\n").unwrap();
+        std::os::unix::fs::symlink(&dir, nested.join("cycle")).unwrap();
+        std::os::unix::fs::symlink(&nested, dir.join("b")).unwrap();
+        std::os::unix::fs::symlink(nested.join("file.txt"), dir.join("link.txt")).unwrap();
+
+        let result = search(&args(&dir, &[])).unwrap();
+        assert_eq!(result.matches.len(), 1);
+        assert!(result.skipped.is_empty());
+
+        let result = search(&args(&dir, &["--follow-symlinks"])).unwrap();
+        // `a/file.txt` is found exactly once, either through `a` or through `b`
+        assert_eq!(result.matches.len(), 2);
+        assert!(result
+            .matches
+            .iter()
+            .any(|m| m.path == dir.join("link.txt")));
+        // `a/cycle` leads back to the root and `a` and `b` are the same directory
+        assert_eq!(result.skipped.len(), 2);
+        assert!(result
+            .skipped
+            .iter()
+            .all(|(_, reason)| *reason == SkipReason::AlreadyVisited));
+    }
 }
@@ -1,20 +1,24 @@
-use crate::messages::{ClientToServerMsg, ServerToClientMsg};
+use crate::messages::{ClientToServerMsg, DeliveryStatus, ServerError, ServerToClientMsg};
 use crate::reader::MessageReader;
 use crate::writer::MessageWriter;
 use crate::SocketWrapper;
 use std::collections::hash_map::Drain;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
 use std::net::TcpStream;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub struct Client {
+    id: u64,
     stream: SocketWrapper,
     reader: Arc<Mutex<MessageReader<ClientToServerMsg, SocketWrapper>>>,
     writer: Arc<Mutex<MessageWriter<ServerToClientMsg, SocketWrapper>>>,
 }
 
 impl Client {
-    pub fn new(stream: TcpStream) -> Self {
+    /// Creates a client for the connection `stream`, identified by `id`.
+    pub fn new(stream: TcpStream, id: u64) -> Self {
         let stream = SocketWrapper(Arc::new(stream));
         let reader = Arc::new(Mutex::new(
             MessageReader::<ClientToServerMsg, SocketWrapper>::new(stream.clone()),
@@ -24,12 +28,17 @@ impl Client {
         ));
 
         Self {
+            id,
             stream,
             reader,
             writer,
         }
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     pub fn send_message(&self, message: ServerToClientMsg) -> anyhow::Result<()> {
         let mut writer = self.writer.lock().unwrap();
         writer.write(message)
@@ -40,6 +49,11 @@ impl Client {
         reader.read()
     }
 
+    /// Sets the timeout of reads from the client, `None` blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.stream.0.set_read_timeout(timeout)
+    }
+
     pub fn disconnect(self, message: Option<ServerToClientMsg>) {
         if let Some(message) = message {
             self.send_message(message).unwrap_or_default();
@@ -57,6 +71,7 @@ impl Clone for Client {
         let reader = self.reader.clone();
         let writer = self.writer.clone();
         Self {
+            id: self.id,
             stream,
             reader,
             writer,
@@ -66,12 +81,26 @@ impl Clone for Client {
 
 pub struct Clients {
     clients: HashMap<String, Client>,
+    /// DMs (sender and message) waiting for users that are not connected
+    offline: HashMap<String, VecDeque<(String, String)>>,
+    offline_queue_size: usize,
+    /// Number of DMs in all offline queues together
+    offline_total: usize,
+    offline_total_size: usize,
 }
 
 impl Clients {
-    pub fn new(capacity: usize) -> Self {
+    /// Creates clients that queue at most `offline_queue_size` DMs for each user that is not
+    /// connected, `0` disables the offline queue.
+    /// At most `offline_total_size` DMs are queued for all users together, so that DMs to made up
+    /// names cannot take an unlimited amount of memory.
+    pub fn new(capacity: usize, offline_queue_size: usize, offline_total_size: usize) -> Self {
         Self {
             clients: HashMap::with_capacity(capacity),
+            offline: HashMap::new(),
+            offline_queue_size,
+            offline_total: 0,
+            offline_total_size,
         }
     }
 
@@ -103,12 +132,60 @@ impl Clients {
     pub fn get_usernames_list(&self) -> Vec<String> {
         self.clients.keys().cloned().collect()
     }
+
+    pub fn queues_offline(&self) -> bool {
+        self.offline_queue_size > 0
+    }
+
+    /// Queues a DM for the user `to` who is not connected.
+    /// Fails if the queue of the user or all queues together are full.
+    pub fn queue_message(
+        &mut self,
+        to: &str,
+        from: String,
+        message: String,
+    ) -> Result<(), ServerError> {
+        let queued = self.offline.get(to).map_or(0, VecDeque::len);
+        if queued >= self.offline_queue_size || self.offline_total >= self.offline_total_size {
+            return Err(ServerError::QueueFull {
+                user: to.to_string(),
+            });
+        }
+        self.offline
+            .entry(to.to_string())
+            .or_default()
+            .push_back((from, message));
+        self.offline_total += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the DMs queued for the user `username`.
+    pub fn take_queued(&mut self, username: &str) -> VecDeque<(String, String)> {
+        let queue = self.offline.remove(username).unwrap_or_default();
+        self.offline_total -= queue.len();
+        queue
+    }
 }
 
 /// Handles the client connection.
-pub fn handle_client(client: Client, clients: Arc<Mutex<Clients>>) {
+/// The client is disconnected if it does not send `Join` within `join_timeout`.
+/// A non-empty `motd` is sent to the client right after `Welcome`.
+pub fn handle_client(
+    client: Client,
+    clients: Arc<Mutex<Clients>>,
+    join_timeout: Duration,
+    motd: &str,
+) {
+    if client.set_read_timeout(Some(join_timeout)).is_err() {
+        client.disconnect(None);
+        return;
+    }
     let username = match client.read_message() {
         Some(Ok(ClientToServerMsg::Join { name })) => name,
+        Some(Err(error)) if is_timeout(&error) => {
+            client.disconnect(Some(ServerError::Timeout.into()));
+            return;
+        }
         _ => {
             client.disconnect(Some(ServerToClientMsg::Error(
                 "Unexpected message received".to_string(),
@@ -117,6 +194,11 @@ pub fn handle_client(client: Client, clients: Arc<Mutex<Clients>>) {
         }
     };
 
+    if client.set_read_timeout(None).is_err() {
+        client.disconnect(None);
+        return;
+    }
+
     {
         let mut clients = clients.lock().unwrap();
         if clients
@@ -132,6 +214,16 @@ pub fn handle_client(client: Client, clients: Arc<Mutex<Clients>>) {
         client
             .send_message(ServerToClientMsg::Welcome)
             .unwrap_or_default();
+        if !motd.is_empty() {
+            client
+                .send_message(ServerToClientMsg::Motd(motd.to_string()))
+                .unwrap_or_default();
+        }
+        for (from, message) in clients.take_queued(&username) {
+            client
+                .send_message(ServerToClientMsg::Message { from, message })
+                .unwrap_or_default();
+        }
     }
 
     while let Some(Ok(message)) = client.read_message() {
@@ -144,6 +236,26 @@ pub fn handle_client(client: Client, clients: Arc<Mutex<Clients>>) {
                 )));
                 return;
             }
+            ClientToServerMsg::Leave => {
+                let mut clients = clients.lock().unwrap();
+                clients.remove_client(&username);
+                for (_, other) in clients.iter() {
+                    other
+                        .send_message(ServerToClientMsg::UserLeft {
+                            name: username.clone(),
+                        })
+                        .unwrap_or_default();
+                }
+                drop(clients);
+                client.disconnect(None);
+                return;
+            }
+            ClientToServerMsg::WhoAmI => client
+                .send_message(ServerToClientMsg::YouAre {
+                    name: username.clone(),
+                    id: client.id(),
+                })
+                .unwrap_or_default(),
             ClientToServerMsg::Ping => client
                 .send_message(ServerToClientMsg::Pong)
                 .unwrap_or_default(),
@@ -165,8 +277,17 @@ pub fn handle_client(client: Client, clients: Arc<Mutex<Clients>>) {
                     continue;
                 }
 
-                let clients = clients.lock().unwrap();
+                let mut clients = clients.lock().unwrap();
                 let result = clients.get_client(&to);
+                if result.is_none() && clients.queues_offline() {
+                    let response = match clients.queue_message(&to, username.clone(), message) {
+                        Ok(()) => ServerToClientMsg::Queued { to },
+                        Err(error) => error.into(),
+                    };
+                    drop(clients);
+                    client.send_message(response).unwrap_or_default();
+                    continue;
+                }
                 drop(clients);
                 match result {
                     Some(to) => {
@@ -188,16 +309,55 @@ pub fn handle_client(client: Client, clients: Arc<Mutex<Clients>>) {
             }
             ClientToServerMsg::Broadcast { message } => {
                 let clients = clients.lock().unwrap();
+                let mut delivered = 0;
                 for (to, client) in clients.iter() {
-                    if &username != to {
-                        client
+                    if &username != to
+                        && client
                             .send_message(ServerToClientMsg::Message {
                                 from: username.clone(),
                                 message: message.clone(),
                             })
-                            .unwrap_or_default();
+                            .is_ok()
+                    {
+                        delivered += 1;
                     }
                 }
+                drop(clients);
+                client
+                    .send_message(ServerToClientMsg::BroadcastAck { delivered })
+                    .unwrap_or_default();
+            }
+            ClientToServerMsg::SendDMMulti { to, message } => {
+                let results = to
+                    .into_iter()
+                    .map(|to| {
+                        if to == username {
+                            return (to, DeliveryStatus::CannotSendToSelf);
+                        }
+
+                        let clients = clients.lock().unwrap();
+                        let result = clients.get_client(&to);
+                        drop(clients);
+                        let status = match result {
+                            Some(recipient) => {
+                                let sent = recipient.send_message(ServerToClientMsg::Message {
+                                    from: username.clone(),
+                                    message: message.clone(),
+                                });
+                                if sent.is_ok() {
+                                    DeliveryStatus::Delivered
+                                } else {
+                                    DeliveryStatus::Failed
+                                }
+                            }
+                            None => DeliveryStatus::NotFound,
+                        };
+                        (to, status)
+                    })
+                    .collect();
+                client
+                    .send_message(ServerToClientMsg::MultiReceipt { results })
+                    .unwrap_or_default();
             }
         }
     }
@@ -205,3 +365,10 @@ pub fn handle_client(client: Client, clients: Arc<Mutex<Clients>>) {
     clients.lock().unwrap().remove_client(&username);
     client.disconnect(None);
 }
+
+/// Returns true if `error` was caused by a read timeout.
+fn is_timeout(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|error| matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut))
+}
@@ -28,6 +28,7 @@ use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 /// Client handling
 mod client;
@@ -44,6 +45,14 @@ mod writer;
 struct ServerOpts {
     /// Maximum number of clients that can be connected to the server at once.
     max_clients: usize,
+    /// How long the server waits for the `Join` message of a new client.
+    join_timeout: Duration,
+    /// Message of the day sent to clients after they join, an empty message is not sent.
+    motd: &'static str,
+    /// Maximum number of DMs queued for a user that is not connected, `0` disables the queue.
+    offline_queue_size: usize,
+    /// Maximum number of DMs queued for all users that are not connected together.
+    offline_total_size: usize,
 }
 
 /// implement the following function called `run_server`
@@ -56,6 +65,8 @@ struct ServerOpts {
 ///
 /// # Client connection
 /// When a client connects to the server, it should send a `Join` message.
+/// If the client does not send a `Join` message within `opts.join_timeout`, the server should
+/// send an error "Timed out waiting for Join" and disconnect the client immediately.
 /// If it sends anything else, the server should respond with an error "Unexpected message received"
 /// and disconnect the client immediately.
 /// If the user sends a Join message (with a unique username), the server should respond with
@@ -64,12 +75,23 @@ struct ServerOpts {
 /// If the client ever sends the `Join` message again, the server should respond with an error
 /// "Unexpected message received" and disconnect the client immediately.
 ///
+/// If `opts.motd` is not empty, the server should send it in a `Motd` message right after
+/// `Welcome`.
+///
 /// # Maximum number of clients
 /// When a client connects and there are already `opts.max_clients` other clients connected, the
 /// server should respond with an error "Server is full" and disconnect the client immediately.
 /// Note that if the server is full, the client should be disconnected even before it sends the
 /// `Join` message.
 ///
+/// # Offline messages
+/// If `opts.offline_queue_size` is not zero, a DM to a user that is not connected is queued
+/// and the server responds with `Queued`. The queued messages are delivered to the user right
+/// after `Welcome` (and `Motd`) when a client with that name joins. When the queue of the user
+/// already contains `opts.offline_queue_size` messages, or all queues together contain
+/// `opts.offline_total_size` messages, the server responds with an error
+/// "Offline queue of user <to> is full".
+///
 /// # Graceful shutdown
 /// When `RunningServer` is dropped, it should:
 /// 1) Stop receiving new TCP/IP connections
@@ -90,12 +112,16 @@ fn run_server(opts: ServerOpts) -> anyhow::Result<RunningServer> {
     let server = std::thread::spawn({
         let end_flag = end_flag.clone();
         move || {
-            let clients = Arc::new(Mutex::new(Clients::new(opts.max_clients)));
+            let clients = Arc::new(Mutex::new(Clients::new(opts.max_clients, opts.offline_queue_size, opts.offline_total_size)));
             let mut connections = vec![];
+            let mut next_id = 0;
 
             for stream in listener.incoming() {
                 let client = match stream {
-                    Ok(stream) => Client::new(stream),
+                    Ok(stream) => {
+                        next_id += 1;
+                        Client::new(stream, next_id)
+                    }
                     Err(e) => {
                         eprintln!("Error: {e}");
                         continue;
@@ -125,7 +151,7 @@ fn run_server(opts: ServerOpts) -> anyhow::Result<RunningServer> {
                 }
                 let connection = std::thread::spawn({
                     let clients = clients.clone();
-                    move || handle_client(client, clients)
+                    move || handle_client(client, clients, opts.join_timeout, opts.motd)
                 });
                 connections.push(connection);
             }
@@ -197,7 +223,7 @@ impl Clone for SocketWrapper {
 
 #[cfg(test)]
 mod tests {
-    use crate::messages::{ClientToServerMsg, ServerToClientMsg};
+    use crate::messages::{ClientToServerMsg, DeliveryStatus, ServerToClientMsg};
     use crate::reader::MessageReader;
     use crate::writer::MessageWriter;
     use crate::{run_server, RunningServer, ServerOpts, SocketWrapper};
@@ -295,6 +321,65 @@ mod tests {
         });
     }
 
+    #[test]
+    fn join_timeout() {
+        let opts = ServerOpts {
+            join_timeout: Duration::from_millis(200),
+            ..opts(1)
+        };
+        run_test(opts, |server| {
+            let mut silent = server.client();
+            silent.expect_error("Timed out waiting for Join");
+            silent.check_closed();
+
+            // The slot of the reaped client can be reused
+            sleep(100);
+            let mut client = server.client();
+            client.join("Foo");
+            client.ping();
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn join_before_timeout() {
+        let opts = ServerOpts {
+            join_timeout: Duration::from_millis(500),
+            ..opts(2)
+        };
+        run_test(opts, |server| {
+            let mut client = server.client();
+            sleep(200);
+            client.join("Foo");
+
+            // The timeout only applies to the Join message
+            sleep(600);
+            client.ping();
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn motd() {
+        let opts = ServerOpts {
+            motd: "Be nice to each other",
+            ..opts(2)
+        };
+        run_test(opts, |server| {
+            let mut client = server.client();
+            client.join("Foo");
+            match client.recv() {
+                ServerToClientMsg::Motd(motd) => assert_eq!(motd, "Be nice to each other"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            client.ping();
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn list_users_before_join() {
         run_test(opts(2), |server| {
@@ -454,6 +539,60 @@ mod tests {
         });
     }
 
+    #[test]
+    fn leave() {
+        run_test(opts(2), |server| {
+            let mut client = server.client();
+            client.join("Terrence");
+
+            let mut client2 = server.client();
+            client2.join("Joe");
+            client2.send(ClientToServerMsg::Leave);
+            client2.check_closed();
+
+            match client.recv() {
+                ServerToClientMsg::UserLeft { name } => assert_eq!(name, "Joe"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            assert_eq!(client.list_users(), vec!["Terrence".to_string()]);
+
+            // The username can be used again
+            let mut client3 = server.client();
+            client3.join("Joe");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn who_am_i() {
+        run_test(opts(2), |server| {
+            let mut client = server.client();
+            client.join("Terrence");
+            let mut client2 = server.client();
+            client2.join("Joe");
+
+            client.send(ClientToServerMsg::WhoAmI);
+            let id = match client.recv() {
+                ServerToClientMsg::YouAre { name, id } => {
+                    assert_eq!(name, "Terrence");
+                    id
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            };
+            client2.send(ClientToServerMsg::WhoAmI);
+            match client2.recv() {
+                ServerToClientMsg::YouAre { name, id: id2 } => {
+                    assert_eq!(name, "Joe");
+                    assert_ne!(id, id2);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn dm_nonexistent_user() {
         run_test(opts(2), |server| {
@@ -466,6 +605,81 @@ mod tests {
         });
     }
 
+    #[test]
+    fn dm_offline_queued() {
+        let opts = ServerOpts {
+            offline_queue_size: 2,
+            ..opts(2)
+        };
+        run_test(opts, |server| {
+            let mut client = server.client();
+            client.join("Mark");
+            for message in ["Hi", "Are you there?", "Hello?"] {
+                client.dm("Fiona", message);
+            }
+            for _ in 0..2 {
+                match client.recv() {
+                    ServerToClientMsg::Queued { to } => assert_eq!(to, "Fiona"),
+                    msg => panic!("Unexpected message {msg:?}"),
+                }
+            }
+            client.expect_error("Offline queue of user Fiona is full");
+
+            let mut fiona = server.client();
+            fiona.join("Fiona");
+            fiona.expect_message("Mark", "Hi");
+            fiona.expect_message("Mark", "Are you there?");
+            fiona.ping();
+            drop(fiona);
+
+            // The queue is emptied by the delivery
+            sleep(100);
+            let mut fiona = server.client();
+            fiona.join("Fiona");
+            fiona.ping();
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn dm_offline_total_limit() {
+        let opts = ServerOpts {
+            offline_queue_size: 2,
+            offline_total_size: 3,
+            ..opts(2)
+        };
+        run_test(opts, |server| {
+            let mut client = server.client();
+            client.join("Mark");
+            for to in ["Fiona", "Fiona", "Joe", "Nobody"] {
+                client.dm(to, "Hi");
+            }
+            for to in ["Fiona", "Fiona", "Joe"] {
+                match client.recv() {
+                    ServerToClientMsg::Queued { to: queued } => assert_eq!(queued, to),
+                    msg => panic!("Unexpected message {msg:?}"),
+                }
+            }
+            client.expect_error("Offline queue of user Nobody is full");
+
+            // Delivering the queued messages makes room for new ones
+            let mut fiona = server.client();
+            fiona.join("Fiona");
+            fiona.expect_message("Mark", "Hi");
+            fiona.expect_message("Mark", "Hi");
+            drop(fiona);
+            sleep(100);
+            client.dm("Nobody", "Hi");
+            match client.recv() {
+                ServerToClientMsg::Queued { to } => assert_eq!(to, "Nobody"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn dm_self() {
         run_test(opts(2), |server| {
@@ -628,9 +842,7 @@ mod tests {
         run_test(opts(2), |server| {
             let mut ji = server.client();
             ji.join("Ji");
-            ji.send(ClientToServerMsg::Broadcast {
-                message: "Haaaaaai!".to_string(),
-            });
+            assert_eq!(ji.broadcast("Haaaaaai!"), 0);
             ji.ping();
 
             Ok(())
@@ -651,9 +863,7 @@ mod tests {
                 })
                 .collect();
 
-            niko.send(ClientToServerMsg::Broadcast {
-                message: "Borrow this!".to_string(),
-            });
+            assert_eq!(niko.broadcast("Borrow this!"), 5);
             niko.ping();
 
             for mut user in users {
@@ -664,6 +874,66 @@ mod tests {
         });
     }
 
+    #[test]
+    fn broadcast_ack() {
+        run_test(opts(10), |server| {
+            let mut terrence = server.client();
+            terrence.join("Terrence");
+            let mut joe = server.client();
+            joe.join("Joe");
+            let mut barbara = server.client();
+            barbara.join("Barbara");
+            // Clients that did not join yet do not receive broadcasts
+            let _anonymous = server.client();
+
+            assert_eq!(terrence.broadcast("Hi all"), 2);
+            joe.expect_message("Terrence", "Hi all");
+            barbara.expect_message("Terrence", "Hi all");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn dm_multi() {
+        run_test(opts(10), |server| {
+            let mut terrence = server.client();
+            terrence.join("Terrence");
+            let mut joe = server.client();
+            joe.join("Joe");
+            let mut ji = server.client();
+            ji.join("Ji");
+
+            terrence.send(ClientToServerMsg::SendDMMulti {
+                to: vec![
+                    "Joe".to_string(),
+                    "Fiona".to_string(),
+                    "Terrence".to_string(),
+                    "Ji".to_string(),
+                ],
+                message: "Hi all".to_string(),
+            });
+            match terrence.recv() {
+                ServerToClientMsg::MultiReceipt { results } => assert_eq!(
+                    results,
+                    vec![
+                        ("Joe".to_string(), DeliveryStatus::Delivered),
+                        ("Fiona".to_string(), DeliveryStatus::NotFound),
+                        ("Terrence".to_string(), DeliveryStatus::CannotSendToSelf),
+                        ("Ji".to_string(), DeliveryStatus::Delivered),
+                    ]
+                ),
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+
+            joe.expect_message("Terrence", "Hi all");
+            ji.expect_message("Terrence", "Hi all");
+            terrence.ping();
+
+            Ok(())
+        });
+    }
+
     // (bonus): uncomment the following test and make it pass
     // The server should correctly close client socket when it shuts down,
     // to avoid a situation where the clients would be stuck waiting for a message
@@ -684,6 +954,40 @@ mod tests {
         assert!(client2.reader.read().is_none());
     }
 
+    #[test]
+    fn compressed_round_trip() {
+        let message = "Borrow this! ".repeat(1000);
+
+        let mut writer = MessageWriter::<ClientToServerMsg, _>::new_compressed(vec![]);
+        writer
+            .write(ClientToServerMsg::Broadcast {
+                message: message.clone(),
+            })
+            .unwrap();
+        writer.write(ClientToServerMsg::Ping).unwrap();
+        let pipe = writer.into_inner();
+
+        let mut reader = MessageReader::<ClientToServerMsg, _>::new_compressed(pipe.as_slice());
+        match reader.read() {
+            Some(Ok(ClientToServerMsg::Broadcast { message: received })) => {
+                assert_eq!(received, message)
+            }
+            msg => panic!("Unexpected message {msg:?}"),
+        }
+        assert!(matches!(reader.read(), Some(Ok(ClientToServerMsg::Ping))));
+        assert!(reader.read().is_none());
+    }
+
+    #[test]
+    fn compressed_frame_rejected_by_plain_reader() {
+        let mut writer = MessageWriter::<ClientToServerMsg, _>::new_compressed(vec![]);
+        writer.write(ClientToServerMsg::Ping).unwrap();
+        let pipe = writer.into_inner();
+
+        let mut reader = MessageReader::<ClientToServerMsg, _>::new(pipe.as_slice());
+        assert!(matches!(reader.read(), Some(Err(_))));
+    }
+
     fn run_test<F: FnOnce(RunningServer) -> anyhow::Result<()>>(opts: ServerOpts, func: F) {
         let server = run_server(opts).expect("creating server failed");
         let port = server.port();
@@ -737,6 +1041,18 @@ mod tests {
             });
         }
 
+        /// Broadcasts a message and returns the number of recipients from the acknowledgement
+        #[track_caller]
+        fn broadcast(&mut self, message: &str) -> usize {
+            self.send(ClientToServerMsg::Broadcast {
+                message: message.to_string(),
+            });
+            match self.recv() {
+                ServerToClientMsg::BroadcastAck { delivered } => delivered,
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
         #[track_caller]
         fn expect_message(&mut self, expected_from: &str, expected_message: &str) {
             let msg = self.recv();
@@ -824,6 +1140,12 @@ mod tests {
     }
 
     fn opts(max_clients: usize) -> ServerOpts {
-        ServerOpts { max_clients }
+        ServerOpts {
+            max_clients,
+            join_timeout: Duration::from_secs(2),
+            motd: "",
+            offline_queue_size: 0,
+            offline_total_size: 100,
+        }
     }
 }
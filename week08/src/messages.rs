@@ -1,3 +1,5 @@
+use std::fmt::{Display, Formatter};
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum ClientToServerMsg {
     /// This is the first message in the communication, which should be sent by the client.
@@ -16,13 +18,38 @@ pub enum ClientToServerMsg {
     /// "Cannot send a DM to yourself".
     SendDM { to: String, message: String },
     /// Sends a message to all currently connected users (except for the sender of the broadcast).
+    /// The server should respond with [ServerToClientMsg::BroadcastAck].
     Broadcast { message: String },
+    /// Sends a direct message to each of the users in `to`.
+    /// The server responds with a single [ServerToClientMsg::MultiReceipt] containing the
+    /// delivery status of every recipient, in the same order as `to`.
+    SendDMMulti { to: Vec<String>, message: String },
+    /// Leaves the chat gracefully. The server removes the user, notifies the remaining users
+    /// with [ServerToClientMsg::UserLeft] and closes the connection.
+    Leave,
+    /// Asks the server for the client's own name and connection ID.
+    /// The server should respond with [ServerToClientMsg::YouAre].
+    WhoAmI,
+}
+
+/// Delivery status of a single recipient of [ClientToServerMsg::SendDMMulti].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Eq, PartialEq)]
+pub enum DeliveryStatus {
+    Delivered,
+    NotFound,
+    /// The recipient was the sender itself.
+    CannotSendToSelf,
+    /// The recipient is connected, but the message could not be sent to it.
+    Failed,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum ServerToClientMsg {
     /// Response to [ClientToServerMsg::Join].
     Welcome,
+    /// Message of the day, sent right after [ServerToClientMsg::Welcome] if the server has one
+    /// configured.
+    Motd(String),
     /// Response to [ClientToServerMsg::Ping].
     Pong,
     /// Response to [ClientToServerMsg::ListUsers].
@@ -30,6 +57,44 @@ pub enum ServerToClientMsg {
     /// This message is sent by the server to a client that should receive a message
     /// (that was sent either by [ClientToServerMsg::SendDM] or [ClientToServerMsg::Broadcast]).
     Message { from: String, message: String },
+    /// Response to [ClientToServerMsg::Broadcast] with the number of users that received the
+    /// message (the sender is not counted).
+    BroadcastAck { delivered: usize },
+    /// Response to [ClientToServerMsg::SendDMMulti].
+    MultiReceipt { results: Vec<(String, DeliveryStatus)> },
+    /// This message is sent by the server to all remaining users when the user `name` leaves
+    /// with [ClientToServerMsg::Leave].
+    UserLeft { name: String },
+    /// Response to [ClientToServerMsg::WhoAmI] with the name the client joined with and the ID
+    /// the server assigned to its connection.
+    YouAre { name: String, id: u64 },
+    /// Response to [ClientToServerMsg::SendDM] to a user `to` who is not connected, if the server
+    /// queues offline messages. The message is delivered once the user joins.
+    Queued { to: String },
     /// This message is returned by the server when an error occurs.
     Error(String),
 }
+
+/// Errors reported to the client through [ServerToClientMsg::Error].
+#[derive(Debug, Eq, PartialEq)]
+pub enum ServerError {
+    /// The client did not send [ClientToServerMsg::Join] in time.
+    Timeout,
+    /// The offline queue of the user is full.
+    QueueFull { user: String },
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::Timeout => write!(f, "Timed out waiting for Join"),
+            ServerError::QueueFull { user } => write!(f, "Offline queue of user {user} is full"),
+        }
+    }
+}
+
+impl From<ServerError> for ServerToClientMsg {
+    fn from(error: ServerError) -> Self {
+        ServerToClientMsg::Error(error.to_string())
+    }
+}
@@ -1,11 +1,15 @@
+use flate2::read::GzDecoder;
 use serde::de::DeserializeOwned;
 use std::io::{ErrorKind, Read};
 use std::marker::PhantomData;
 
 const MAX_MESSAGE_SIZE: u32 = 256;
+/// Maximum size of a decompressed message, protects against decompression bombs.
+const MAX_DECOMPRESSED_SIZE: u64 = 1024 * 1024;
 
 pub struct MessageReader<T, R> {
     stream: R,
+    compressed: bool,
     _phantom: PhantomData<T>,
 }
 
@@ -13,10 +17,19 @@ impl<T: DeserializeOwned, R: Read> MessageReader<T, R> {
     pub fn new(read: R) -> Self {
         Self {
             stream: read,
+            compressed: false,
             _phantom: Default::default(),
         }
     }
 
+    /// Creates a reader for frames written by [`crate::writer::MessageWriter::new_compressed`].
+    pub fn new_compressed(read: R) -> Self {
+        Self {
+            compressed: true,
+            ..Self::new(read)
+        }
+    }
+
     pub fn read(&mut self) -> Option<anyhow::Result<T>> {
         // Read message size
         let mut message = [0; 4];
@@ -40,6 +53,21 @@ impl<T: DeserializeOwned, R: Read> MessageReader<T, R> {
             return Some(Err(anyhow::anyhow!("Cannot read message: {error:?}")));
         }
 
+        // Decompress message
+        if self.compressed {
+            let mut decompressed = vec![];
+            let mut decoder = GzDecoder::new(buffer.as_slice()).take(MAX_DECOMPRESSED_SIZE + 1);
+            if let Err(error) = decoder.read_to_end(&mut decompressed) {
+                return Some(Err(anyhow::anyhow!(
+                    "Cannot decompress message: {error:?}"
+                )));
+            }
+            if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+                return Some(Err(anyhow::anyhow!("Decompressed message too large")));
+            }
+            buffer = decompressed;
+        }
+
         // Deserialize message from JSON
         match serde_json::from_slice::<T>(&buffer) {
             Ok(msg) => Some(Ok(msg)),
@@ -1,9 +1,12 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use serde::Serialize;
 use std::io::Write;
 use std::marker::PhantomData;
 
 pub struct MessageWriter<T, W> {
     sink: W,
+    compressed: bool,
     _phantom: PhantomData<T>,
 }
 
@@ -11,13 +14,30 @@ impl<W: Write, T: Serialize> MessageWriter<T, W> {
     pub fn new(write: W) -> Self {
         Self {
             sink: write,
+            compressed: false,
             _phantom: Default::default(),
         }
     }
 
+    /// Creates a writer that gzip-compresses the payload of each frame.
+    /// The frames can only be read by [`crate::reader::MessageReader::new_compressed`].
+    pub fn new_compressed(write: W) -> Self {
+        Self {
+            compressed: true,
+            ..Self::new(write)
+        }
+    }
+
     pub fn write(&mut self, message: T) -> anyhow::Result<()> {
         // Serialize the data
-        let serialized = serde_json::to_vec(&message)?;
+        let mut serialized = serde_json::to_vec(&message)?;
+
+        // Compress the data
+        if self.compressed {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(&serialized)?;
+            serialized = encoder.finish()?;
+        }
 
         // Write size
         let size = serialized.len() as u32;
@@ -46,6 +46,8 @@ mod writer;
 struct ServerOpts {
     /// Maximum number of clients that can be connected to the server at once.
     max_clients: usize,
+    /// Message of the day sent to clients after they join, an empty message is not sent.
+    motd: &'static str,
 }
 
 /// implement the following function called `run_server`
@@ -72,6 +74,9 @@ struct ServerOpts {
 /// If the client ever sends the `Join` message again, the server should respond with an error
 /// "Unexpected message received" and disconnect the client immediately.
 ///
+/// If `opts.motd` is not empty, the server should send it in a `Motd` message right after
+/// `Welcome`.
+///
 /// # Maximum number of clients
 /// When a client connects and there are already `opts.max_clients` other clients connected, the
 /// server should respond with an error "Server is full" and disconnect the client immediately.
@@ -134,7 +139,7 @@ mod tests {
     use crate::reader::MessageReader;
     use crate::writer::MessageWriter;
     use crate::{run_server, RunningServer, ServerOpts};
-    use std::io::{Read, Write};
+    use std::io::{ErrorKind, Read, Write};
     use std::net::{Shutdown, TcpStream};
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::{Arc, Barrier, Mutex};
@@ -240,6 +245,35 @@ mod tests {
         });
     }
 
+    #[test]
+    fn who_am_i() {
+        run_test(opts(2), |server| {
+            let mut client = server.client();
+            client.join("Foo");
+            let mut client2 = server.client();
+            client2.join("Bar");
+
+            client.send(ClientToServerMsg::WhoAmI);
+            let id = match client.recv() {
+                ServerToClientMsg::YouAre { name, id } => {
+                    assert_eq!(name, "Foo");
+                    id
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            };
+            client2.send(ClientToServerMsg::WhoAmI);
+            match client2.recv() {
+                ServerToClientMsg::YouAre { name, id: id2 } => {
+                    assert_eq!(name, "Bar");
+                    assert_ne!(id, id2);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn join_after_half_sec() {
         run_test(opts(2), |server| {
@@ -630,6 +664,84 @@ mod tests {
         });
     }
 
+    #[test]
+    fn reader_fragmented_frame() {
+        /// Returns a single byte per read, with `WouldBlock` in between
+        struct Trickle {
+            data: Vec<u8>,
+            position: usize,
+            blocked: bool,
+        }
+
+        impl Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.blocked = !self.blocked;
+                if self.blocked {
+                    return Err(ErrorKind::WouldBlock.into());
+                }
+                let Some(byte) = self.data.get(self.position) else {
+                    return Ok(0);
+                };
+                buf[0] = *byte;
+                self.position += 1;
+                Ok(1)
+            }
+        }
+
+        let mut reader = MessageReader::<ClientToServerMsg, _>::new(Trickle {
+            data: b"{\"Join\":{\"name\":\"Frag\"}}\n".to_vec(),
+            position: 0,
+            blocked: false,
+        });
+
+        let mut received = vec![];
+        while let Some(msg) = reader.recv() {
+            match msg {
+                Ok(msg) => received.push(msg),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => continue,
+                Err(error) => panic!("Unexpected error {error:?}"),
+            }
+        }
+        assert!(matches!(
+            received.as_slice(),
+            [ClientToServerMsg::Join { name }] if name == "Frag"
+        ));
+    }
+
+    #[test]
+    fn motd() {
+        let opts = ServerOpts {
+            motd: "Be nice to each other",
+            ..opts(2)
+        };
+        run_test(opts, |server| {
+            let mut client = server.client();
+            client.join("Foo");
+            match client.recv() {
+                ServerToClientMsg::Motd(motd) => assert_eq!(motd, "Be nice to each other"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            client.ping();
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn join_fragmented() {
+        run_test(opts(2), |server| {
+            let mut client = server.client();
+            for byte in b"{\"Join\":{\"name\":\"Frag\"}}\n" {
+                client.writer.inner().0.as_ref().write_all(&[*byte])?;
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            assert!(matches!(client.recv(), ServerToClientMsg::Welcome));
+            client.ping();
+
+            Ok(())
+        });
+    }
+
     // The server should correctly close client socket when it shuts down,
     // to avoid a situation where the clients would be stuck waiting for a message
     // for some indeterminate amount of time.
@@ -790,6 +902,9 @@ mod tests {
     }
 
     fn opts(max_clients: usize) -> ServerOpts {
-        ServerOpts { max_clients }
+        ServerOpts {
+            max_clients,
+            motd: "",
+        }
     }
 }
@@ -17,12 +17,18 @@ pub enum ClientToServerMsg {
     SendDM { to: String, message: String },
     /// Sends a message to all currently connected users (except for the sender of the broadcast).
     Broadcast { message: String },
+    /// Asks the server for the client's own name and connection ID.
+    /// The server should respond with [ServerToClientMsg::YouAre].
+    WhoAmI,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum ServerToClientMsg {
     /// Response to [ClientToServerMsg::Join].
     Welcome,
+    /// Message of the day, sent right after [ServerToClientMsg::Welcome] if the server has one
+    /// configured.
+    Motd(String),
     /// Response to [ClientToServerMsg::Ping].
     Pong,
     /// Response to [ClientToServerMsg::ListUsers].
@@ -30,6 +36,9 @@ pub enum ServerToClientMsg {
     /// This message is sent by the server to a client that should receive a message
     /// (that was sent either by [ClientToServerMsg::SendDM] or [ClientToServerMsg::Broadcast]).
     Message { from: String, message: String },
+    /// Response to [ClientToServerMsg::WhoAmI] with the name the client joined with and the ID
+    /// the server assigned to its connection.
+    YouAre { name: String, id: u64 },
     /// This message is returned by the server when an error occurs.
     Error(String),
 }
@@ -21,6 +21,10 @@ impl<T: DeserializeOwned, R: Read> MessageReader<T, R> {
         }
     }
 
+    /// Receives the next newline-delimited message.
+    ///
+    /// Bytes of an incomplete message are kept in the internal buffer when the underlying stream
+    /// returns an error (e.g. `WouldBlock`), so the next call continues where this one stopped.
     pub fn recv(&mut self) -> Option<std::io::Result<T>> {
         loop {
             if let Some(position) = self.buffer[..self.loaded].iter().position(|c| *c == b'\n') {
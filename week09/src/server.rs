@@ -85,7 +85,7 @@ pub fn server_loop(
                         continue;
                     };
                     let mut stream = client.stream();
-                    if let Some(client) = handle_client(client, &mut clients) {
+                    if let Some(client) = handle_client(token, client, &mut clients, opts.motd) {
                         clients.insert(token, client);
                     } else if let Some(stream) = Rc::get_mut(&mut stream) {
                         poll.registry().deregister(stream).unwrap_or_default();
@@ -153,7 +153,15 @@ impl Clients {
     }
 }
 
-pub fn handle_client(mut client: Client, clients: &mut Clients) -> Option<Client> {
+/// Handles all messages that are available from the `client`.
+/// A non-empty `motd` is sent to the client right after `Welcome`.
+/// The `token` of the client's connection is reported to it as its ID.
+pub fn handle_client(
+    token: Token,
+    mut client: Client,
+    clients: &mut Clients,
+    motd: &str,
+) -> Option<Client> {
     if client.username().is_none() {
         match client.read_message() {
             Some(Ok(ClientToServerMsg::Join { name })) => {
@@ -166,6 +174,11 @@ pub fn handle_client(mut client: Client, clients: &mut Clients) -> Option<Client
 
                 client.set_username(name);
                 client.send_message(ServerToClientMsg::Welcome).ok()?;
+                if !motd.is_empty() {
+                    client
+                        .send_message(ServerToClientMsg::Motd(motd.to_string()))
+                        .ok()?;
+                }
             }
             Some(Err(error)) if error.kind() == ErrorKind::WouldBlock => return Some(client),
             _ => {
@@ -189,6 +202,15 @@ pub fn handle_client(mut client: Client, clients: &mut Clients) -> Option<Client
                 ClientToServerMsg::Ping => {
                     client.send_message(ServerToClientMsg::Pong).ok()?;
                 }
+                ClientToServerMsg::WhoAmI => {
+                    let name = client.username().unwrap().to_string();
+                    client
+                        .send_message(ServerToClientMsg::YouAre {
+                            name,
+                            id: token.0 as u64,
+                        })
+                        .ok()?;
+                }
                 ClientToServerMsg::ListUsers => {
                     let mut users = clients.get_usernames_list();
                     users.push(client.username().unwrap().to_string());
@@ -43,47 +43,319 @@
 //! You can use e.g. [`tokio::task::JoinSet`] to execute N futures concurrently, periodically
 //! read results of resolved futures, and add new futures.
 
+use anyhow::{anyhow, Context};
+use clap::Parser;
 use futures::StreamExt;
 use humansize::BINARY;
+use reqwest::Response;
+use sha2::{Digest, Sha256};
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Component, Path, PathBuf};
 use std::rc::Rc;
 use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 use tokio::task::LocalSet;
 use tokio::time::Instant;
+use tracing::Instrument;
 use url::Url;
 
+#[derive(Debug)]
 struct DownloadEntry {
-    url: Url,
+    /// The primary URL followed by its mirrors, tried in this order
+    urls: Vec<Url>,
     file_name: String,
 }
 
-fn main() -> anyhow::Result<()> {
-    let links: Vec<DownloadEntry> = std::fs::read_to_string("links-small.txt")?
+/// Parses a links file with one `URL [MIRROR...]` or `URL [MIRROR...]<TAB>filename` per line.
+///
+/// Without an explicit file name, the last segment of the primary URL path is used. Such automatic
+/// names that collide with a previous entry get a `-1`, `-2`, ... suffix (before the extension).
+/// An explicit file name must be a plain name within the destination directory (no path
+/// separators, `.` or `..`) that is not used by a previous entry.
+fn parse_links(content: &str) -> anyhow::Result<Vec<DownloadEntry>> {
+    let mut used = HashSet::new();
+    content
         .lines()
-        .map(|s| {
-            let url = Url::parse(s)?;
-            let file_name = url.path_segments().unwrap().last().unwrap().to_string();
-            Ok(DownloadEntry { url, file_name })
+        .map(|line| {
+            let (urls, file_name) = match line.split_once('\t') {
+                Some((urls, file_name)) => (urls, Some(file_name.to_string())),
+                None => (line, None),
+            };
+            let urls = urls
+                .split_whitespace()
+                .map(Url::parse)
+                .collect::<Result<Vec<_>, _>>()?;
+            let Some(primary) = urls.first() else {
+                anyhow::bail!("No URL on line `{line}`");
+            };
+            let file_name = match file_name {
+                Some(file_name) => {
+                    if !is_plain_name(&file_name) {
+                        anyhow::bail!("Invalid file name `{file_name}` on line `{line}`");
+                    }
+                    if used.contains(&file_name) {
+                        anyhow::bail!("File name `{file_name}` on line `{line}` is already used");
+                    }
+                    file_name
+                }
+                None => {
+                    let file_name = primary.path_segments().unwrap().next_back().unwrap();
+                    unique_name(file_name, &used)
+                }
+            };
+            used.insert(file_name.clone());
+            Ok(DownloadEntry { urls, file_name })
         })
-        .collect::<anyhow::Result<Vec<_>>>()?;
+        .collect()
+}
+
+/// Returns true if `name` names a file directly inside a directory, i.e. it is not empty, absolute,
+/// `.` or `..` and does not contain path separators.
+fn is_plain_name(name: &str) -> bool {
+    let components: Vec<_> = Path::new(name).components().collect();
+    matches!(components.as_slice(), [Component::Normal(component)] if *component == name)
+}
+
+/// Returns `name`, or `name` with the first free `-N` suffix if it is already `used`.
+fn unique_name(name: &str, used: &HashSet<String>) -> String {
+    if !used.contains(name) {
+        return name.to_string();
+    }
+
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let extension = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|extension| format!(".{extension}"))
+        .unwrap_or_default();
+    (1..)
+        .map(|i| format!("{stem}-{i}{extension}"))
+        .find(|candidate| !used.contains(candidate))
+        .unwrap()
+}
+
+/// Errors of a single download that are detected by the download manager itself.
+#[derive(Debug)]
+enum DownloadError {
+    /// The server sent a different number of bytes than it declared in `Content-Length`
+    LengthMismatch { expected: u64, received: u64 },
+    /// The download did not finish within the per-link timeout
+    Timeout(Duration),
+    /// The run was cancelled before the download finished
+    Cancelled,
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::LengthMismatch { expected, received } => write!(
+                f,
+                "Content-Length mismatch: expected {expected} bytes, received {received} bytes"
+            ),
+            DownloadError::Timeout(timeout) => write!(f, "Timed out after {timeout:?}"),
+            DownloadError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+/// Returns true if `error` is [`DownloadError::Cancelled`].
+fn is_cancelled(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref(), Some(DownloadError::Cancelled))
+}
+
+/// Files whose declared length is below this size (in bytes) are downloaded to memory.
+const SMALL_FILE_SIZE: u64 = 64 * 1024;
+
+/// Name of the manifest file in the destination directory.
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// Downloads completed in the destination directory, so that an interrupted batch can be resumed.
+/// It is stored as JSON in [`MANIFEST_NAME`] and updated after each finished download.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Manifest {
+    /// Completed downloads by their file names
+    completed: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct ManifestEntry {
+    /// URL (primary or mirror) the file was downloaded from
+    url: String,
+    size: u64,
+    /// Hex-encoded SHA-256 of the file contents
+    sha256: String,
+}
+
+impl Manifest {
+    /// Loads the manifest from `dest`, a missing manifest is empty.
+    async fn load(dest: &Path) -> anyhow::Result<Self> {
+        match tokio::fs::read(dest.join(MANIFEST_NAME)).await {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Writes the manifest to `dest` atomically, so that an interrupted run cannot corrupt it.
+    async fn save(&self, dest: &Path) -> anyhow::Result<()> {
+        let partial = dest.join(format!("{MANIFEST_NAME}.part"));
+        tokio::fs::write(&partial, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(partial, dest.join(MANIFEST_NAME)).await?;
+        Ok(())
+    }
 
-    let dest = PathBuf::from("downloads");
-    if dest.is_dir() {
+    /// Returns true if `link` was downloaded into `dest` by a previous run and the file still has
+    /// the recorded size.
+    async fn is_completed(&self, link: &DownloadEntry, dest: &Path) -> bool {
+        let Some(entry) = self.completed.get(&link.file_name) else {
+            return false;
+        };
+        if !link.urls.iter().any(|url| url.as_str() == entry.url) {
+            return false;
+        }
+        tokio::fs::metadata(dest.join(&link.file_name))
+            .await
+            .is_ok_and(|metadata| metadata.len() == entry.size)
+    }
+}
+
+/// Machine-readable summary of a [`download_files`] run.
+#[derive(Debug, serde::Serialize)]
+struct RunSummary {
+    total_files: usize,
+    succeeded: usize,
+    /// File names of the downloads skipped because a previous run completed them
+    skipped: Vec<String>,
+    /// File names of the failed downloads together with the error message
+    failed: Vec<(String, String)>,
+    /// File names of the downloads that were not finished because the run was cancelled
+    incomplete: Vec<String>,
+    /// File names of the successful downloads together with the URL (primary or mirror) they
+    /// were downloaded from
+    sources: Vec<(String, String)>,
+    /// Number of bytes downloaded over all files
+    bytes: u64,
+    duration_secs: f64,
+}
+
+/// Download all links from a file into a directory
+#[derive(Parser, Debug, PartialEq)]
+struct Args {
+    /// File with one `URL [MIRROR...]` or `URL [MIRROR...]<TAB>filename` per line
+    #[arg(long, default_value = "links-small.txt")]
+    links: PathBuf,
+    /// Directory to download the files into
+    #[arg(long, default_value = "downloads")]
+    dest: PathBuf,
+    /// Maximum number of files downloaded at once
+    #[arg(
+        long,
+        default_value_t = 4,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    concurrency: usize,
+    /// Download again the files completed by a previous run, overwriting them
+    #[arg(long)]
+    overwrite: bool,
+    /// Remove the destination directory (with the downloads of previous runs) before downloading
+    #[arg(long)]
+    fresh: bool,
+    /// Fail a link that does not finish downloading within the given number of seconds
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+    /// Write the summary of the run to the given file as JSON
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+impl Args {
+    fn download_options(&self) -> DownloadOptions {
+        DownloadOptions {
+            link_timeout: self.timeout,
+            concurrency: self.concurrency,
+            overwrite: self.overwrite,
+            cancellation: Cancellation::default(),
+        }
+    }
+}
+
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds.parse().map_err(|error| format!("{error}"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|error| error.to_string())
+}
+
+fn main() -> anyhow::Result<()> {
+    // Log events are filtered with `RUST_LOG`, e.g. `RUST_LOG=info` (the default) or
+    // `RUST_LOG=[download{file=data.bin}]=debug` to see the progress of a single file.
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::builder()
+                .with_default_directive(tracing::Level::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let args = Args::parse();
+    if !args.links.is_file() {
+        anyhow::bail!("Links file {} does not exist", args.links.display());
+    }
+    let links = parse_links(&std::fs::read_to_string(&args.links)?)?;
+
+    let dest = args.dest.clone();
+    if args.fresh && dest.is_dir() {
         std::fs::remove_dir_all(&dest)?;
     }
+    std::fs::create_dir_all(&dest)
+        .with_context(|| format!("Cannot create destination directory {}", dest.display()))?;
+    let options = args.download_options();
+    let cancellation = options.cancellation.clone();
 
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()?;
 
-    let start = Instant::now();
-    runtime.block_on(async move {
+    let summary = runtime.block_on(async move {
         let localset = LocalSet::new();
-        localset.run_until(download_files(links, dest)).await
+        localset.spawn_local(async move {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            tracing::warn!("Cancelling, waiting for the running downloads to stop");
+            tracing::warn!("Press Ctrl-C again to exit immediately");
+            cancellation.cancel();
+            // Once registered, the handler captures SIGINT for the rest of the process, so the
+            // default behaviour of a second Ctrl-C has to be restored manually
+            if tokio::signal::ctrl_c().await.is_ok() {
+                tracing::warn!("Exiting without waiting for the running downloads");
+                std::process::exit(130);
+            }
+        });
+        localset
+            .run_until(download_files(links, dest, options))
+            .await
     })?;
-    println!("Duration: {:.2}s", start.elapsed().as_secs_f64());
+    println!(
+        "Downloaded {}/{} files ({}) in {:.2}s, {} already downloaded",
+        summary.succeeded,
+        summary.total_files,
+        humansize::format_size(summary.bytes, BINARY),
+        summary.duration_secs,
+        summary.skipped.len()
+    );
+    for (file_name, error) in &summary.failed {
+        println!("Failed: {file_name}: {error}");
+    }
+    for file_name in &summary.incomplete {
+        println!("Incomplete: {file_name}");
+    }
+    if let Some(report) = args.report {
+        std::fs::write(report, serde_json::to_string_pretty(&summary)?)?;
+    }
 
     Ok(())
 }
@@ -150,54 +422,922 @@ async fn download_files(links: Vec<DownloadEntry>, dest: PathBuf) -> anyhow::Res
 }
 */
 
-async fn download_files(links: Vec<DownloadEntry>, dest: PathBuf) -> anyhow::Result<()> {
+/// Requests the `urls` in order and returns the first successful response, together with the URL
+/// that provided it.
+async fn fetch(client: &reqwest::Client, urls: &[Url]) -> anyhow::Result<(Url, Response)> {
+    let mut last_error = anyhow!("No URL to download");
+    for url in urls {
+        match client
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(Response::error_for_status)
+        {
+            Ok(response) => return Ok((url.clone(), response)),
+            Err(error) => last_error = error.into(),
+        }
+    }
+    Err(last_error)
+}
+
+/// Options of a [`download_files`] run.
+#[derive(Debug, PartialEq)]
+struct DownloadOptions {
+    /// A link that does not finish downloading within this time fails with
+    /// [`DownloadError::Timeout`].
+    link_timeout: Option<Duration>,
+    /// Maximum number of links downloaded at once
+    concurrency: usize,
+    /// Download again the links completed by a previous run instead of skipping them
+    overwrite: bool,
+    /// Stops the run when cancelled, see [`Cancellation`]
+    cancellation: Cancellation,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            link_timeout: None,
+            concurrency: 1,
+            overwrite: false,
+            cancellation: Cancellation::default(),
+        }
+    }
+}
+
+/// Shared flag used to stop a [`download_files`] run, e.g. on Ctrl-C.
+/// Running downloads wait for it together with the network, so even a stalled download stops
+/// and closes its files cleanly, links that have not started yet are not downloaded at all.
+#[derive(Debug, Clone)]
+struct Cancellation(Rc<tokio::sync::watch::Sender<bool>>);
+
+impl Cancellation {
+    fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Completes once the cancellation is cancelled.
+    async fn cancelled(&self) {
+        let mut receiver = self.0.subscribe();
+        // The sender lives in `self`, so the channel cannot be closed while waiting
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        Self(Rc::new(tokio::sync::watch::channel(false).0))
+    }
+}
+
+impl PartialEq for Cancellation {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_cancelled() == other.is_cancelled()
+    }
+}
+
+/// Downloads all `links` into `dest`.
+/// A failed download does not stop the run, it is recorded in the returned [`RunSummary`].
+/// Links completed by a previous run into the same `dest` (according to its [`Manifest`]) are
+/// skipped, unless `options.overwrite` is set.
+/// Up to `options.concurrency` links are downloaded at once, the summary lists them in the order
+/// of `links`.
+/// Each link is downloaded in a `download` span carrying its file name.
+/// Once `options.cancellation` is cancelled, the unfinished links are listed as incomplete in the
+/// summary and their `.part` files are removed.
+async fn download_files(
+    links: Vec<DownloadEntry>,
+    dest: PathBuf,
+    options: DownloadOptions,
+) -> anyhow::Result<RunSummary> {
     tokio::fs::create_dir_all(&dest).await?;
 
+    let start = Instant::now();
     let client = reqwest::Client::new();
+    let mut manifest = Manifest::load(&dest).await?;
+    let mut summary = RunSummary {
+        total_files: links.len(),
+        succeeded: 0,
+        skipped: vec![],
+        failed: vec![],
+        incomplete: vec![],
+        sources: vec![],
+        bytes: 0,
+        duration_secs: 0.0,
+    };
+    let mut pending = Vec::with_capacity(links.len());
     for link in links {
-        let response = client.get(link.url).send().await?;
+        if !options.overwrite && manifest.is_completed(&link, &dest).await {
+            tracing::info_span!("download", file = %link.file_name)
+                .in_scope(|| tracing::info!("Already downloaded, skipping"));
+            summary.skipped.push(link.file_name);
+        } else {
+            pending.push(link);
+        }
+    }
 
-        let size = response.content_length().unwrap_or(0);
-        println!(
-            "Downloading: {} ({})",
-            link.file_name,
-            humansize::format_size(response.content_length().unwrap_or(0), BINARY)
-        );
+    let (client, dest_ref, cancellation) = (&client, &dest, &options.cancellation);
+    let mut downloads = futures::stream::iter(pending)
+        .map(|link| async move {
+            // Links that have not started before the cancellation are not downloaded at all
+            let result = if cancellation.is_cancelled() {
+                Err(DownloadError::Cancelled.into())
+            } else {
+                download_link(client, &link, dest_ref, options.link_timeout, cancellation).await
+            };
+            (link, result)
+        })
+        .buffered(options.concurrency.max(1));
+    while let Some((link, result)) = downloads.next().await {
+        match result {
+            Ok(entry) => {
+                summary.succeeded += 1;
+                summary.bytes += entry.size;
+                summary
+                    .sources
+                    .push((link.file_name.clone(), entry.url.clone()));
+                manifest.completed.insert(link.file_name, entry);
+                manifest.save(&dest).await?;
+            }
+            Err(error) if is_cancelled(&error) => summary.incomplete.push(link.file_name),
+            Err(error) => summary.failed.push((link.file_name, error.to_string())),
+        }
+    }
+    summary.duration_secs = start.elapsed().as_secs_f64();
 
-        let byte_counter = Rc::new(RefCell::new(0));
-        let byte_counter2 = byte_counter.clone();
+    Ok(summary)
+}
 
-        let (tx, mut rx) = tokio::sync::mpsc::channel(256);
-        let network_downloader = async move {
-            let mut stream = response.bytes_stream();
-            while let Some(Ok(chunk)) = stream.next().await {
-                *byte_counter.borrow_mut() += chunk.len() as u64;
-                tx.send(chunk).await.unwrap_or_default();
+/// Downloads a single `link` into `dest` within a `download` span, failing with
+/// [`DownloadError::Timeout`] if it does not finish within `timeout`.
+async fn download_link(
+    client: &reqwest::Client,
+    link: &DownloadEntry,
+    dest: &Path,
+    timeout: Option<Duration>,
+    cancellation: &Cancellation,
+) -> anyhow::Result<ManifestEntry> {
+    let span = tracing::info_span!("download", file = %link.file_name);
+    let download = download_file(client, link, dest, cancellation).instrument(span.clone());
+    let result = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, download).await {
+            Ok(result) => result,
+            Err(_) => {
+                // The download was interrupted, so it did not clean up after itself
+                let _ = tokio::fs::remove_file(partial_path(dest, &link.file_name)).await;
+                Err(DownloadError::Timeout(timeout).into())
             }
-        };
-        let dest = dest.join(link.file_name);
-        let disk_writer = async move {
-            let mut file = tokio::fs::File::create(dest).await?;
-            while let Some(chunk) = rx.recv().await {
-                file.write_all(&chunk).await?;
+        },
+        None => download.await,
+    };
+    span.in_scope(|| match &result {
+        Ok(entry) => tracing::info!(url = %entry.url, bytes = entry.size, "Download completed"),
+        Err(error) => tracing::error!(%error, "Download failed"),
+    });
+    result
+}
+
+/// Downloads a single `link` into `dest`.
+/// Returns the manifest entry of the downloaded file.
+///
+/// The file is first written to a `.part` file, which is renamed to the final name only once the
+/// download succeeds and its length matches `Content-Length` (if the server declared it).
+/// Otherwise, the `.part` file is removed.
+/// The download stops with [`DownloadError::Cancelled`] as soon as `cancellation` is cancelled,
+/// even while waiting for the server.
+/// Files smaller than [`SMALL_FILE_SIZE`] are downloaded to memory and written at once, larger
+/// files are streamed to disk while downloading.
+async fn download_file(
+    client: &reqwest::Client,
+    link: &DownloadEntry,
+    dest: &Path,
+    cancellation: &Cancellation,
+) -> anyhow::Result<ManifestEntry> {
+    let (url, response) = tokio::select! {
+        fetched = fetch(client, &link.urls) => fetched?,
+        _ = cancellation.cancelled() => return Err(DownloadError::Cancelled.into()),
+    };
+
+    let content_length = response.content_length();
+    let size = content_length.unwrap_or(0);
+    tracing::info!(
+        %url,
+        size = %humansize::format_size(size, BINARY),
+        "Download started"
+    );
+
+    let partial = partial_path(dest, &link.file_name);
+    let dest = dest.join(&link.file_name);
+    let (bytes, downloaded, written) = match content_length {
+        Some(length) if length < SMALL_FILE_SIZE => {
+            download_to_memory(response, &partial, cancellation).await
+        }
+        _ => download_streaming(response, &partial, size, cancellation).await,
+    };
+
+    // A stream that ends early is reported as a length mismatch rather than as a network error,
+    // unless it was stopped by the cancellation
+    let result = written.and_then(|sha256| match content_length {
+        _ if downloaded.as_ref().is_err_and(is_cancelled) => downloaded.map(|()| sha256),
+        Some(expected) if expected != bytes => Err(DownloadError::LengthMismatch {
+            expected,
+            received: bytes,
+        }
+        .into()),
+        _ => downloaded.map(|()| sha256),
+    });
+    let sha256 = match result {
+        Ok(sha256) => sha256,
+        Err(error) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(error);
+        }
+    };
+    tokio::fs::rename(&partial, &dest).await?;
+
+    Ok(ManifestEntry {
+        url: url.to_string(),
+        size: bytes,
+        sha256,
+    })
+}
+
+/// Path of the file that `file_name` is downloaded to in `dest` before it is complete.
+fn partial_path(dest: &Path, file_name: &str) -> PathBuf {
+    dest.join(format!("{file_name}.part"))
+}
+
+/// Downloads the whole `response` to memory and then writes it to `partial` at once.
+/// Stops with [`DownloadError::Cancelled`] as soon as `cancellation` is cancelled.
+/// Returns the number of received bytes, the result of the download and the SHA-256 of the
+/// written file.
+async fn download_to_memory(
+    response: Response,
+    partial: &Path,
+    cancellation: &Cancellation,
+) -> (u64, anyhow::Result<()>, anyhow::Result<String>) {
+    let mut body = vec![];
+    let mut downloaded = Ok(());
+    let mut stream = response.bytes_stream();
+    loop {
+        let chunk = tokio::select! {
+            chunk = stream.next() => chunk,
+            _ = cancellation.cancelled() => {
+                downloaded = Err(DownloadError::Cancelled.into());
+                break;
             }
-            Ok::<(), anyhow::Error>(())
         };
+        match chunk {
+            Some(Ok(chunk)) => body.extend_from_slice(&chunk),
+            Some(Err(error)) => {
+                downloaded = Err(error.into());
+                break;
+            }
+            None => break,
+        }
+    }
+    let written = tokio::fs::write(partial, &body)
+        .await
+        .map(|()| hex_digest(Sha256::new_with_prefix(&body)))
+        .map_err(Into::into);
+
+    (body.len() as u64, downloaded, written)
+}
 
-        let mut download_fut =
-            std::pin::pin!(futures::future::join(network_downloader, disk_writer));
+/// Downloads the `response` of `size` bytes to `partial`, overlapping the network download with
+/// writing the chunks to disk.
+/// Once `cancellation` is cancelled, the download stops with [`DownloadError::Cancelled`]
+/// immediately, while the chunks received so far are still written and the file is flushed.
+/// Returns the number of received bytes, the result of the download and the SHA-256 of the
+/// written file.
+async fn download_streaming(
+    response: Response,
+    partial: &Path,
+    size: u64,
+    cancellation: &Cancellation,
+) -> (u64, anyhow::Result<()>, anyhow::Result<String>) {
+    let byte_counter = Rc::new(RefCell::new(0));
+    let byte_counter2 = byte_counter.clone();
 
+    let (tx, mut rx) = tokio::sync::mpsc::channel(256);
+    let network_downloader = async move {
+        let mut stream = response.bytes_stream();
         loop {
-            tokio::select! {
-                _ = &mut download_fut => {
-                    break;
-                },
-                _ = tokio::time::sleep(Duration::from_millis(500)) => {
-                    println!("Progress: {}/{size}", byte_counter2.borrow());
-                }
+            let chunk = tokio::select! {
+                chunk = stream.next() => chunk,
+                _ = cancellation.cancelled() => return Err(DownloadError::Cancelled.into()),
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
+            let chunk = chunk?;
+            *byte_counter.borrow_mut() += chunk.len() as u64;
+            tx.send(chunk).await.unwrap_or_default();
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+    let disk_writer = async move {
+        let mut file = tokio::fs::File::create(partial).await?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = rx.recv().await {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok::<String, anyhow::Error>(hex_digest(hasher))
+    };
+
+    let mut download_fut = std::pin::pin!(futures::future::join(network_downloader, disk_writer));
+
+    let (downloaded, written) = loop {
+        tokio::select! {
+            result = &mut download_fut => {
+                break result;
+            },
+            _ = tokio::time::sleep(Duration::from_millis(500)) => {
+                tracing::debug!(bytes = *byte_counter2.borrow(), size, "Progress");
             }
         }
+    };
+
+    let bytes = *byte_counter2.borrow();
+    (bytes, downloaded, written)
+}
+
+fn hex_digest(hasher: Sha256) -> String {
+    let hash = hasher.finalize();
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        download_files, download_streaming, download_to_memory, parse_links, Args, Cancellation,
+        DownloadOptions, Manifest, ManifestEntry,
+    };
+    use clap::Parser;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use url::Url;
+
+    fn file_names(content: &str) -> Vec<String> {
+        parse_links(content)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.file_name)
+            .collect()
+    }
+
+    #[test]
+    fn name_from_url() {
+        assert_eq!(
+            file_names("https://example.com/files/a.zip\nhttps://example.com/b.zip"),
+            vec!["a.zip", "b.zip"]
+        );
+    }
+
+    #[test]
+    fn name_override() {
+        let links = parse_links("https://example.com/files/a.zip\tarchive.zip").unwrap();
+        assert_eq!(links[0].urls[0].as_str(), "https://example.com/files/a.zip");
+        assert_eq!(links[0].file_name, "archive.zip");
+    }
+
+    #[test]
+    fn name_override_invalid() {
+        for name in ["../../x", "/etc/x", "dir/x", "..", ".", "", "x/."] {
+            let line = format!("https://example.com/files/a.zip\t{name}");
+            assert!(parse_links(&line).is_err(), "{name} accepted");
+        }
+        assert!(parse_links("https://example.com/a.zip\t..x.zip").is_ok());
     }
 
-    Ok(())
+    #[test]
+    fn name_override_collision() {
+        let content = "https://a.com/data.bin\nhttps://b.com/x\tdata.bin";
+        assert!(parse_links(content).is_err());
+        let content = "https://a.com/x\tdata.bin\nhttps://b.com/y\tdata.bin";
+        assert!(parse_links(content).is_err());
+        // Automatic names still avoid the explicit ones
+        let content = "https://a.com/x\tdata.bin\nhttps://b.com/data.bin";
+        assert_eq!(file_names(content), vec!["data.bin", "data-1.bin"]);
+    }
+
+    #[test]
+    fn name_collision() {
+        let content = [
+            "https://a.com/data.bin",
+            "https://b.com/data.bin",
+            "https://c.com/other\tdata-2.bin",
+            "https://d.com/data.bin",
+            "https://e.com/archive",
+            "https://f.com/archive",
+        ]
+        .join("\n");
+        assert_eq!(
+            file_names(&content),
+            vec![
+                "data.bin",
+                "data-1.bin",
+                "data-2.bin",
+                "data-3.bin",
+                "archive",
+                "archive-1"
+            ]
+        );
+    }
+
+    #[test]
+    fn mirrors() {
+        let links =
+            parse_links("https://a.com/data.bin https://b.com/mirror.bin\nhttps://c.com/data.bin")
+                .unwrap();
+        let urls: Vec<_> = links[0].urls.iter().map(Url::as_str).collect();
+        assert_eq!(
+            urls,
+            vec!["https://a.com/data.bin", "https://b.com/mirror.bin"]
+        );
+        assert_eq!(links[0].file_name, "data.bin");
+        assert_eq!(links[1].file_name, "data-1.bin");
+    }
+
+    #[test]
+    fn invalid_url() {
+        assert!(parse_links("not a url").is_err());
+        assert!(parse_links("\tname.txt").is_err());
+    }
+
+    #[test]
+    fn cli_args() {
+        let args = Args::try_parse_from([
+            "download",
+            "--links",
+            "links-medium.txt",
+            "--dest",
+            "/tmp/out",
+            "--concurrency",
+            "8",
+            "--overwrite",
+            "--timeout",
+            "1.5",
+        ])
+        .unwrap();
+        assert_eq!(
+            args,
+            Args {
+                links: PathBuf::from("links-medium.txt"),
+                dest: PathBuf::from("/tmp/out"),
+                concurrency: 8,
+                overwrite: true,
+                fresh: false,
+                timeout: Some(Duration::from_millis(1500)),
+                report: None,
+            }
+        );
+        assert_eq!(
+            args.download_options(),
+            DownloadOptions {
+                link_timeout: Some(Duration::from_millis(1500)),
+                concurrency: 8,
+                overwrite: true,
+                cancellation: Cancellation::default(),
+            }
+        );
+
+        let args = Args::try_parse_from(["download"]).unwrap();
+        assert_eq!(args.links, PathBuf::from("links-small.txt"));
+        assert_eq!(args.dest, PathBuf::from("downloads"));
+        assert_eq!(args.concurrency, 4);
+        assert!(!args.overwrite);
+
+        assert!(Args::try_parse_from(["download", "--concurrency", "0"]).is_err());
+        assert!(Args::try_parse_from(["download", "--timeout", "-1"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrent_overwrite() {
+        let first = serve(b"first").await;
+        let second = serve(b"second").await;
+        let third = serve(b"third").await;
+        let links = format!("{first}\n{second}\n{third}");
+        let dest =
+            std::env::temp_dir().join(format!("concurrent-overwrite-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dest);
+        let options = || DownloadOptions {
+            concurrency: 3,
+            overwrite: true,
+            ..DownloadOptions::default()
+        };
+
+        let summary = download_files(parse_links(&links).unwrap(), dest.clone(), options())
+            .await
+            .unwrap();
+        assert_eq!(summary.succeeded, 3);
+        let names: Vec<_> = summary
+            .sources
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["file.txt", "file-1.txt", "file-2.txt"]);
+
+        // Completed files are downloaded again
+        std::fs::write(dest.join("file-1.txt"), b"changed").unwrap();
+        let summary = download_files(parse_links(&links).unwrap(), dest.clone(), options())
+            .await
+            .unwrap();
+        assert!(summary.skipped.is_empty());
+        assert_eq!(summary.succeeded, 3);
+        assert_eq!(std::fs::read(dest.join("file-1.txt")).unwrap(), b"second");
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mirror_fallback() {
+        let dead = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            format!("http://{}/file.txt", listener.local_addr().unwrap())
+        };
+        let mirror = serve(b"mirrored").await;
+        let links = parse_links(&format!("{dead} {mirror}")).unwrap();
+
+        let dest = std::env::temp_dir().join(format!("mirror-fallback-{}", std::process::id()));
+        let summary = download_files(links, dest.clone(), DownloadOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            summary.sources,
+            vec![("file.txt".to_string(), mirror.to_string())]
+        );
+        assert_eq!(std::fs::read(dest.join("file.txt")).unwrap(), b"mirrored");
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn summary_mixed_run() {
+        let dead = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            format!("http://{}/file.txt", listener.local_addr().unwrap())
+        };
+        let first = serve(b"first").await;
+        let second = serve(b"second").await;
+        let links = parse_links(&format!("{first}\n{dead}\n{second}")).unwrap();
+
+        let dest = std::env::temp_dir().join(format!("summary-mixed-{}", std::process::id()));
+        let summary = download_files(links, dest.clone(), DownloadOptions::default())
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(dest).unwrap();
+
+        assert_eq!(summary.total_files, 3);
+        assert_eq!(summary.succeeded, 2);
+        assert_eq!(summary.bytes, 11);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, "file-1.txt");
+
+        let json: serde_json::Value = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["total_files"], 3);
+        assert_eq!(json["succeeded"], 2);
+        assert_eq!(json["failed"][0][0], "file-1.txt");
+        assert_eq!(json["bytes"], 11);
+    }
+
+    #[tokio::test]
+    async fn length_mismatch() {
+        let short = serve_with_length(b"short", 10).await;
+        let complete = serve(b"complete").await;
+        let links = parse_links(&format!("{short}\n{complete}")).unwrap();
+
+        let dest = std::env::temp_dir().join(format!("length-mismatch-{}", std::process::id()));
+        let summary = download_files(links, dest.clone(), DownloadOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(summary.succeeded, 1);
+        assert_eq!(
+            summary.failed,
+            vec![(
+                "file.txt".to_string(),
+                "Content-Length mismatch: expected 10 bytes, received 5 bytes".to_string()
+            )]
+        );
+        // The truncated file is not committed, the other download is not affected
+        assert!(!dest.join("file.txt").exists());
+        assert!(!dest.join("file.txt.part").exists());
+        assert_eq!(std::fs::read(dest.join("file-1.txt")).unwrap(), b"complete");
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_from_manifest() {
+        let dead = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            format!("http://{}/file.txt", listener.local_addr().unwrap())
+        };
+        let first = serve(b"first").await;
+        let links = format!("{first}\n{dead}");
+        let dest = std::env::temp_dir().join(format!("resume-manifest-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dest);
+
+        let summary = download_files(
+            parse_links(&links).unwrap(),
+            dest.clone(),
+            DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.succeeded, 1);
+        assert!(summary.skipped.is_empty());
+        let manifest = Manifest::load(&dest).await.unwrap();
+        assert_eq!(
+            manifest.completed.into_iter().collect::<Vec<_>>(),
+            vec![(
+                "file.txt".to_string(),
+                ManifestEntry {
+                    url: first.to_string(),
+                    size: 5,
+                    sha256: "a7937b64b8caa58f03721bb6bacf5c78cb235febe0e70b1b84cd99541461a08e"
+                        .to_string()
+                }
+            )]
+        );
+
+        // The completed link is skipped, the failed one is retried
+        let summary = download_files(
+            parse_links(&links).unwrap(),
+            dest.clone(),
+            DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.skipped, vec!["file.txt"]);
+        assert_eq!(summary.succeeded, 0);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, "file-1.txt");
+
+        // A completed file that was removed is downloaded again
+        std::fs::remove_file(dest.join("file.txt")).unwrap();
+        let summary = download_files(
+            parse_links(&links).unwrap(),
+            dest.clone(),
+            DownloadOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(summary.skipped.is_empty());
+        assert_eq!(summary.succeeded, 1);
+        assert_eq!(std::fs::read(dest.join("file.txt")).unwrap(), b"first");
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn small_and_streaming_identical() {
+        let body: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let url = serve(body.clone().leak()).await;
+        let dest = std::env::temp_dir().join(format!("small-streaming-{}", std::process::id()));
+        std::fs::create_dir_all(&dest).unwrap();
+
+        let response = reqwest::get(url.clone()).await.unwrap();
+        let (bytes, downloaded, memory_hash) =
+            download_to_memory(response, &dest.join("memory"), &Cancellation::default()).await;
+        assert_eq!(bytes, body.len() as u64);
+        downloaded.unwrap();
+
+        let response = reqwest::get(url).await.unwrap();
+        let (bytes, downloaded, streaming_hash) = download_streaming(
+            response,
+            &dest.join("streaming"),
+            body.len() as u64,
+            &Cancellation::default(),
+        )
+        .await;
+        assert_eq!(bytes, body.len() as u64);
+        downloaded.unwrap();
+
+        assert_eq!(memory_hash.unwrap(), streaming_hash.unwrap());
+        assert_eq!(std::fs::read(dest.join("memory")).unwrap(), body);
+        assert_eq!(std::fs::read(dest.join("streaming")).unwrap(), body);
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn link_timeout() {
+        let stalled = serve_stalled().await;
+        let complete = serve(b"complete").await;
+        let links = parse_links(&format!("{stalled}\n{complete}")).unwrap();
+
+        let dest = std::env::temp_dir().join(format!("link-timeout-{}", std::process::id()));
+        let summary = download_files(
+            links,
+            dest.clone(),
+            DownloadOptions {
+                link_timeout: Some(Duration::from_millis(200)),
+                ..DownloadOptions::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            summary.failed,
+            vec![("file.txt".to_string(), "Timed out after 200ms".to_string())]
+        );
+        assert_eq!(summary.succeeded, 1);
+        assert!(!dest.join("file.txt.part").exists());
+        assert_eq!(std::fs::read(dest.join("file-1.txt")).unwrap(), b"complete");
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancellation() {
+        let complete = serve(b"complete").await;
+        let slow = serve_slowly().await;
+        let links = parse_links(&format!("{complete}\n{slow}\n{slow}")).unwrap();
+        let dest = std::env::temp_dir().join(format!("cancellation-{}", std::process::id()));
+        let cancellation = Cancellation::default();
+        let options = DownloadOptions {
+            cancellation: cancellation.clone(),
+            ..DownloadOptions::default()
+        };
+
+        let (summary, ()) =
+            futures::future::join(download_files(links, dest.clone(), options), async {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                cancellation.cancel();
+            })
+            .await;
+        let summary = summary.unwrap();
+
+        assert_eq!(summary.succeeded, 1);
+        assert!(summary.failed.is_empty());
+        assert_eq!(summary.incomplete, vec!["file-1.txt", "file-2.txt"]);
+        // Neither partial nor truncated files are left behind
+        let mut files: Vec<_> = std::fs::read_dir(&dest)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["file.txt", "manifest.json"]);
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancellation_stalled() {
+        let stalled = serve_stalled().await;
+        let unresponsive = serve_unresponsive().await;
+        let links = parse_links(&format!("{stalled}\n{unresponsive}")).unwrap();
+        let dest =
+            std::env::temp_dir().join(format!("cancellation-stalled-{}", std::process::id()));
+        let cancellation = Cancellation::default();
+        let options = DownloadOptions {
+            concurrency: 2,
+            cancellation: cancellation.clone(),
+            ..DownloadOptions::default()
+        };
+
+        // Neither download receives any data, so only the cancellation itself can stop them
+        let run = futures::future::join(download_files(links, dest.clone(), options), async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            cancellation.cancel();
+        });
+        let (summary, ()) = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .expect("The cancelled run did not stop");
+        let summary = summary.unwrap();
+
+        assert_eq!(summary.succeeded, 0);
+        assert!(summary.failed.is_empty());
+        assert_eq!(summary.incomplete, vec!["file.txt", "file-1.txt"]);
+        assert!(!dest.join("file.txt.part").exists());
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[tokio::test]
+    async fn completion_events() {
+        let logs = Arc::new(Mutex::new(vec![]));
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || LogWriter(writer.clone()))
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let first = serve(b"first").await;
+        let second = serve(b"second").await;
+        let links = parse_links(&format!("{first}\n{second}")).unwrap();
+        let dest = std::env::temp_dir().join(format!("completion-events-{}", std::process::id()));
+        let summary = download_files(links, dest.clone(), DownloadOptions::default())
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(dest).unwrap();
+        assert_eq!(summary.succeeded, 2);
+
+        let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+        let completed = |file: &str| {
+            logs.lines()
+                .filter(|line| line.contains(&format!("download{{file={file}}}")))
+                .filter(|line| line.contains("Download completed"))
+                .count()
+        };
+        assert_eq!(completed("file.txt"), 1);
+        assert_eq!(completed("file-1.txt"), 1);
+    }
+
+    /// Writer that appends the formatted log lines to a shared buffer
+    struct LogWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Starts an HTTP server that responds to every request with `body`
+    async fn serve(body: &'static [u8]) -> Url {
+        serve_with_length(body, body.len()).await
+    }
+
+    /// Starts an HTTP server that responds to every request with `body`, declaring
+    /// `content_length` as its length
+    async fn serve_with_length(body: &'static [u8], content_length: usize) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.txt", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![];
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {content_length}\r\nConnection: close\r\n\r\n"
+                );
+                let _ = stream.write_all(header.as_bytes()).await;
+                let _ = stream.write_all(body).await;
+            }
+        });
+        Url::parse(&url).unwrap()
+    }
+
+    /// Starts an HTTP server that sends a large response in small chunks with pauses in-between,
+    /// without ever finishing it
+    async fn serve_slowly() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.txt", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let header = "HTTP/1.1 200 OK\r\nContent-Length: 1000000\r\n\r\n";
+                    let _ = stream.write_all(header.as_bytes()).await;
+                    while stream.write_all(&[0; 100]).await.is_ok() {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+                });
+            }
+        });
+        Url::parse(&url).unwrap()
+    }
+
+    /// Starts an HTTP server that sends only the response header and then stalls
+    async fn serve_stalled() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.txt", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let header = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n";
+                let _ = stream.write_all(header.as_bytes()).await;
+                connections.push(stream);
+            }
+        });
+        Url::parse(&url).unwrap()
+    }
+
+    /// Starts an HTTP server that accepts connections, but never responds
+    async fn serve_unresponsive() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.txt", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+        Url::parse(&url).unwrap()
+    }
 }
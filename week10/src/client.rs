@@ -1,31 +1,44 @@
 #![allow(unused)]
 use crate::messages::ClientToServerMsg;
 use crate::messages::ServerToClientMsg;
+use crate::messages::{ChatLine, Features, ServerError, PROTOCOL_VERSION};
+use crate::rooms::{RoomLimits, Rooms};
 use crate::reader::MessageReader;
 use crate::writer::MessageWriter;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::io::AsyncWriteExt;
 use tokio::net::tcp::OwnedReadHalf;
 use tokio::net::tcp::OwnedWriteHalf;
 use tokio::net::TcpStream;
 use tokio::select;
 use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+
+/// Minimal interval between two typing indicators relayed from the same client.
+const TYPING_INTERVAL: Duration = Duration::from_secs(1);
 
 pub struct Client {
+    id: u64,
     writer: MessageWriter<ServerToClientMsg, OwnedWriteHalf>,
     reader: MessageReader<ClientToServerMsg, OwnedReadHalf>,
 }
 
 impl Client {
-    pub fn new(stream: TcpStream) -> Self {
+    /// Creates a client for the connection `stream`, identified by `id`.
+    pub fn new(stream: TcpStream, id: u64) -> Self {
         let (read, write) = stream.into_split();
         let writer = MessageWriter::<ServerToClientMsg, OwnedWriteHalf>::new(write);
         let reader = MessageReader::<ClientToServerMsg, OwnedReadHalf>::new(read);
 
-        Self { writer, reader }
+        Self { id, writer, reader }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
     pub async fn send_message(&mut self, message: ServerToClientMsg) -> anyhow::Result<()> {
@@ -36,6 +49,12 @@ impl Client {
         self.reader.recv().await
     }
 
+    /// Switches the connection to compressed frames in both directions.
+    pub fn set_compressed(&mut self, compressed: bool) {
+        self.reader.set_compressed(compressed);
+        self.writer.set_compressed(compressed);
+    }
+
     pub async fn disconnect(mut self, message: Option<ServerToClientMsg>) {
         if let Some(message) = message {
             self.send_message(message).await.unwrap_or_default();
@@ -49,49 +68,221 @@ impl Client {
 }
 
 pub struct Clients {
-    clients: HashMap<String, Sender<ServerToClientMsg>>,
+    /// Senders of the sessions of each user, keyed by the ID of the client connection
+    clients: HashMap<String, HashMap<u64, Sender<ServerToClientMsg>>>,
+    rooms: Rooms,
+    /// Whether more clients can join with the same username
+    multi_session: bool,
+    /// When the server started
+    started: Instant,
+    /// Number of messages delivered to their recipients
+    messages_routed: u64,
+    /// The latest broadcasts, oldest first
+    history: VecDeque<ChatLine>,
+    /// Maximum number of broadcasts kept in `history`
+    history_size: usize,
 }
 
 impl Clients {
-    pub fn new(capacity: usize) -> Self {
+    /// Creates clients that allow more sessions with the same username if `multi_session` is set
+    /// and keep a history of the latest `history_size` broadcasts.
+    pub fn new(
+        capacity: usize,
+        room_limits: RoomLimits,
+        multi_session: bool,
+        history_size: usize,
+    ) -> Self {
         Self {
             clients: HashMap::with_capacity(capacity),
+            rooms: Rooms::new(room_limits),
+            multi_session,
+            started: Instant::now(),
+            messages_routed: 0,
+            history: VecDeque::with_capacity(history_size),
+            history_size,
         }
     }
 
-    pub fn add_client(&mut self, username: String, client: Sender<ServerToClientMsg>) -> bool {
-        if self.clients.contains_key(&username) {
+    /// Adds the session `id` of the user `username`.
+    /// Returns true if the username is already taken (and more sessions are not allowed).
+    pub fn add_client(
+        &mut self,
+        username: String,
+        id: u64,
+        client: Sender<ServerToClientMsg>,
+    ) -> bool {
+        if self.clients.contains_key(&username) && !self.multi_session {
             return true;
         }
 
-        self.clients.insert(username, client).is_some()
+        self.clients
+            .entry(username)
+            .or_default()
+            .insert(id, client)
+            .is_some()
     }
 
-    pub fn remove_client(&mut self, username: &str) {
+    /// Removes the session `id` of the client. Once its last session is removed, its memberships
+    /// in rooms are removed as well.
+    /// Returns true if the last session was removed, i.e. the user is no longer joined.
+    pub fn remove_client(&mut self, username: &str, id: u64) -> bool {
+        let Some(sessions) = self.clients.get_mut(username) else {
+            return false;
+        };
+        sessions.remove(&id);
+        if !sessions.is_empty() {
+            return false;
+        }
         self.clients.remove(username);
+        self.rooms.leave_all(username);
+        true
     }
 
-    pub fn get_client(&self, username: &str) -> Option<Sender<ServerToClientMsg>> {
-        self.clients.get(username).cloned()
+    pub fn rooms(&mut self) -> &mut Rooms {
+        &mut self.rooms
     }
 
+    /// Returns the senders of all members of the `room` except for `username`, or `None` if
+    /// `username` is not a member of the room.
+    pub fn get_room_clients(
+        &self,
+        room: &str,
+        username: &str,
+    ) -> Option<Vec<Sender<ServerToClientMsg>>> {
+        let members = self.rooms.members(room)?;
+        if !members.iter().any(|member| member == username) {
+            return None;
+        }
+        Some(
+            members
+                .iter()
+                .filter(|member| *member != username)
+                .flat_map(|member| self.get_client(member))
+                .collect(),
+        )
+    }
+
+    /// Returns the senders of all sessions of the user, the result is empty if the user does not
+    /// exist.
+    pub fn get_client(&self, username: &str) -> Vec<Sender<ServerToClientMsg>> {
+        self.clients
+            .get(username)
+            .map(|sessions| sessions.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the senders of all sessions together with their usernames.
     pub fn get_all_clients(&self) -> Vec<(String, Sender<ServerToClientMsg>)> {
         self.clients
             .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
+            .flat_map(|(k, sessions)| sessions.values().map(|v| (k.clone(), v.clone())))
             .collect()
     }
 
-    pub fn get_usernames_list(&self) -> Vec<String> {
-        self.clients.keys().cloned().collect()
+    /// Returns the usernames together with their number of sessions.
+    pub fn get_usernames_list(&self) -> Vec<(String, usize)> {
+        self.clients
+            .iter()
+            .map(|(k, sessions)| (k.clone(), sessions.len()))
+            .collect()
+    }
+
+    /// Returns at most `limit` users (with their number of sessions) sorted by username, starting
+    /// at `offset`, together with the total number of users.
+    pub fn get_usernames_page(&self, offset: usize, limit: usize) -> (Vec<(String, usize)>, usize) {
+        let mut users = self.get_usernames_list();
+        users.sort_unstable();
+        let total = users.len();
+        let page = users.into_iter().skip(offset).take(limit).collect();
+        (page, total)
+    }
+
+    /// Adds a broadcast to the history, dropping the oldest one if the history is full.
+    pub fn record_broadcast(&mut self, line: ChatLine) {
+        if self.history_size == 0 {
+            return;
+        }
+        if self.history.len() == self.history_size {
+            self.history.pop_front();
+        }
+        self.history.push_back(line);
+    }
+
+    /// Returns the latest broadcasts, oldest first.
+    pub fn history(&self) -> Vec<ChatLine> {
+        self.history.iter().cloned().collect()
+    }
+
+    /// Records that `count` messages were delivered to their recipients.
+    pub fn count_routed(&mut self, count: usize) {
+        self.messages_routed += count as u64;
+    }
+
+    /// Returns the [ServerToClientMsg::Stats] of the server.
+    pub fn stats(&self) -> ServerToClientMsg {
+        ServerToClientMsg::Stats {
+            connected: self.clients.values().map(HashMap::len).sum(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            messages_routed: self.messages_routed,
+        }
     }
 
     pub fn clear(&mut self) {
         self.clients.clear();
+        self.rooms.clear();
     }
 }
 
-pub async fn handle_client(mut client: Client, clients: Rc<RefCell<Clients>>) {
+/// Shared access to [`Clients`] from multiple client handlers.
+///
+/// The borrow is only held for the duration of `f`, so it can never be held across an `await`.
+pub trait SharedClients: Clone {
+    fn with<R>(&self, f: impl FnOnce(&mut Clients) -> R) -> R;
+}
+
+impl SharedClients for Rc<RefCell<Clients>> {
+    fn with<R>(&self, f: impl FnOnce(&mut Clients) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+impl SharedClients for Arc<Mutex<Clients>> {
+    fn with<R>(&self, f: impl FnOnce(&mut Clients) -> R) -> R {
+        f(&mut self.lock().unwrap())
+    }
+}
+
+/// Handles the client connection on a single-threaded runtime (use with `spawn_local`).
+pub async fn handle_client(client: Client, clients: Rc<RefCell<Clients>>, motd: &str) {
+    serve_client(client, clients, motd).await
+}
+
+/// Handles the client connection on a multi-threaded runtime (use with `tokio::spawn`).
+pub async fn handle_client_mt(client: Client, clients: Arc<Mutex<Clients>>, motd: &str) {
+    serve_client(client, clients, motd).await
+}
+
+/// Serves the client connection, a non-empty `motd` is sent right after `Welcome`.
+async fn serve_client<C: SharedClients>(mut client: Client, clients: C, motd: &str) {
+    // The server supports all the features, so it enables those requested by the client
+    let features: Features = select! {
+        message = client.read_message() => match message {
+            Some(Ok(ClientToServerMsg::Hello { protocol_version, features })) if protocol_version == PROTOCOL_VERSION => features,
+            Some(Ok(ClientToServerMsg::Hello { protocol_version, .. })) => {
+                client.disconnect(Some(ServerError::UnsupportedVersion { requested: protocol_version, supported: PROTOCOL_VERSION }.into())).await;
+                return;
+            }
+            _ => {
+                client.disconnect(Some(ServerToClientMsg::Error("Unexpected message received".to_string()))).await;
+                return;
+            }
+        },
+        _ = tokio::time::sleep(Duration::from_secs(2)) => {
+            client.disconnect(Some(ServerToClientMsg::Error("Timed out waiting for Hello".to_string()))).await;
+            return;
+        }
+    };
+
     let username = select! {
         message = client.read_message() => match message {
             Some(Ok(ClientToServerMsg::Join { name })) => name,
@@ -108,7 +299,11 @@ pub async fn handle_client(mut client: Client, clients: Rc<RefCell<Clients>>) {
 
     let (tx, mut rx) = tokio::sync::mpsc::channel::<ServerToClientMsg>(1024);
 
-    let result = { clients.borrow_mut().add_client(username.clone(), tx) };
+    // The history is taken together with joining, so that every later broadcast is delivered live
+    let (result, history) = clients.with(|clients| {
+        let result = clients.add_client(username.clone(), client.id(), tx);
+        (result, clients.history())
+    });
     if result {
         client
             .disconnect(Some(ServerToClientMsg::Error(
@@ -119,24 +314,82 @@ pub async fn handle_client(mut client: Client, clients: Rc<RefCell<Clients>>) {
     }
 
     client
-        .send_message(ServerToClientMsg::Welcome)
+        .send_message(ServerToClientMsg::Welcome { features })
         .await
         .unwrap_or_default();
+    client.set_compressed(features.compression);
+    if !motd.is_empty() {
+        client
+            .send_message(ServerToClientMsg::Motd(motd.to_string()))
+            .await
+            .unwrap_or_default();
+    }
+    if !history.is_empty() {
+        client
+            .send_message(ServerToClientMsg::HistoryBatch(history))
+            .await
+            .unwrap_or_default();
+    }
 
+    let mut deadline = Instant::now();
+    let mut refresh = true;
+    // When the last typing indicator of this client was relayed
+    let mut last_typing: Option<Instant> = None;
+    // Sequence number of the last `Message` delivered to this client
+    let mut last_seq = 0;
     let message = loop {
+        if refresh {
+            deadline = Instant::now() + Duration::from_secs(3);
+        }
+        refresh = true;
         select! {
             message = rx.recv() => match message {
-                Some(message) => client.send_message(message).await.unwrap_or_default(),
+                Some(mut message) => {
+                    // Server time pushes do not count as activity of the client
+                    refresh = !matches!(message, ServerToClientMsg::ServerTime(_));
+                    // The senders do not know the sequence numbers of the recipient
+                    if let ServerToClientMsg::Message{ seq, .. } = &mut message {
+                        last_seq += 1;
+                        *seq = last_seq;
+                    }
+                    client.send_message(message).await.unwrap_or_default()
+                }
                 None => break None,
             },
             message = client.read_message() => match message {
                 Some(Ok(message)) => match message {
-                    ClientToServerMsg::Join{ .. } => break Some(ServerToClientMsg::Error("Unexpected message received".to_string())),
+                    ClientToServerMsg::Hello{ .. } | ClientToServerMsg::Join{ .. } => break Some(ServerToClientMsg::Error("Unexpected message received".to_string())),
+                    ClientToServerMsg::Leave => {
+                        // The other sessions of the user keep it joined, so nobody is notified yet
+                        let others = clients.with(|clients| {
+                            if clients.remove_client(&username, client.id()) {
+                                clients.get_all_clients()
+                            } else {
+                                vec![]
+                            }
+                        });
+                        for (_, sender) in others {
+                            sender.send(ServerToClientMsg::UserLeft{ name: username.clone() }).await.unwrap_or_default();
+                        }
+                        break None;
+                    }
+                    ClientToServerMsg::WhoAmI => {
+                        let id = client.id();
+                        client.send_message(ServerToClientMsg::YouAre{ name: username.clone(), id }).await.unwrap_or_default();
+                    }
                     ClientToServerMsg::Ping => client.send_message(ServerToClientMsg::Pong).await.unwrap_or_default(),
+                    ClientToServerMsg::ServerStats => {
+                        let stats = clients.with(|clients| clients.stats());
+                        client.send_message(stats).await.unwrap_or_default();
+                    }
                     ClientToServerMsg::ListUsers => {
-                        let users = clients.borrow().get_usernames_list();
+                        let users = clients.with(|clients| clients.get_usernames_list());
                         client.send_message(ServerToClientMsg::UserList{ users }).await.unwrap_or_default();
                     }
+                    ClientToServerMsg::ListUsersPage{ offset, limit } => {
+                        let (users, total) = clients.with(|clients| clients.get_usernames_page(offset, limit));
+                        client.send_message(ServerToClientMsg::UserListPage{ users, total }).await.unwrap_or_default();
+                    }
                     ClientToServerMsg::SendDM{to,message  } => {
                         if to == username {
                             client.send_message(ServerToClientMsg::Error(
@@ -144,36 +397,149 @@ pub async fn handle_client(mut client: Client, clients: Rc<RefCell<Clients>>) {
                         )).await.unwrap_or_default();
                         continue;
                         }
-                        let sender = clients.borrow().get_client(&to);
-                        if let Some(sender) = sender {
-                            sender.send(ServerToClientMsg::Message{ from: username.clone(), message }).await.unwrap_or_default();
-                        } else {
+                        let senders = clients.with(|clients| clients.get_client(&to));
+                        let mut delivered = 0;
+                        for sender in &senders {
+                            if sender.send(ServerToClientMsg::Message{ from: username.clone(), message: message.clone(), seq: 0 }).await.is_ok() {
+                                delivered += 1;
+                            }
+                        }
+                        clients.with(|clients| clients.count_routed(delivered));
+                        if senders.is_empty() {
                             client.send_message(ServerToClientMsg::Error(format!(
                                 "User {} does not exist",
                                 to
                             ))).await.unwrap_or_default();
                         }
                     }
+                    ClientToServerMsg::FileOffer{ to, name, size } => {
+                        let message = ServerToClientMsg::IncomingFile{ from: username.clone(), name, size };
+                        relay_file_message(&mut client, &clients, &username, &to, message).await;
+                    }
+                    ClientToServerMsg::FileAccept{ from } => {
+                        let message = ServerToClientMsg::FileAccepted{ by: username.clone() };
+                        relay_file_message(&mut client, &clients, &username, &from, message).await;
+                    }
+                    ClientToServerMsg::FileReject{ from } => {
+                        let message = ServerToClientMsg::FileRejected{ by: username.clone() };
+                        relay_file_message(&mut client, &clients, &username, &from, message).await;
+                    }
+                    ClientToServerMsg::Typing{ to } => {
+                        if last_typing.is_some_and(|last| last.elapsed() < TYPING_INTERVAL) || to == username {
+                            continue;
+                        }
+                        let senders = clients.with(|clients| clients.get_client(&to));
+                        if !senders.is_empty() {
+                            last_typing = Some(Instant::now());
+                        }
+                        for sender in senders {
+                            sender.send(ServerToClientMsg::PeerTyping{ from: username.clone() }).await.unwrap_or_default();
+                        }
+                    }
+                    ClientToServerMsg::JoinRoom{ room } => {
+                        let message = match clients.with(|clients| clients.rooms().join(&room, &username)) {
+                            Ok(()) => ServerToClientMsg::RoomJoined{ room },
+                            Err(error) => error.into(),
+                        };
+                        client.send_message(message).await.unwrap_or_default();
+                    }
+                    ClientToServerMsg::LeaveRoom{ room } => {
+                        let message = if clients.with(|clients| clients.rooms().leave(&room, &username)) {
+                            ServerToClientMsg::RoomLeft{ room }
+                        } else {
+                            ServerError::NotInRoom{ room }.into()
+                        };
+                        client.send_message(message).await.unwrap_or_default();
+                    }
+                    ClientToServerMsg::RoomBroadcast{ room, message } => {
+                        let Some(members) = clients.with(|clients| clients.get_room_clients(&room, &username)) else {
+                            client.send_message(ServerError::NotInRoom{ room }.into()).await.unwrap_or_default();
+                            continue;
+                        };
+                        let mut delivered = 0;
+                        for sender in members {
+                            if sender.send(ServerToClientMsg::RoomMessage{ room: room.clone(), from: username.clone(), message: message.clone() }).await.is_ok() {
+                                delivered += 1;
+                            }
+                        }
+                        clients.with(|clients| clients.count_routed(delivered));
+                    }
                     ClientToServerMsg::Broadcast{  message } => {
-                        let clients = clients.borrow().get_all_clients();
-                        for (to, sender) in clients {
+                        let senders = clients.with(|clients| {
+                            clients.record_broadcast(ChatLine{ from: username.clone(), message: message.clone() });
+                            clients.get_all_clients()
+                        });
+                        let mut delivered = 0;
+                        for (to, sender) in senders {
                             if to == username {
                                 continue;
                             }
-                            sender.send(ServerToClientMsg::Message{ from: username.clone(), message: message.clone() }).await.unwrap_or_default();
+                            if sender.send(ServerToClientMsg::Message{ from: username.clone(), message: message.clone(), seq: 0 }).await.is_ok() {
+                                delivered += 1;
+                            }
                         }
+                        clients.with(|clients| clients.count_routed(delivered));
+                        client.send_message(ServerToClientMsg::BroadcastAck { delivered }).await.unwrap_or_default();
                     }
                 },
                 _ => break None,
             },
-            _ = tokio::time::sleep(Duration::from_secs(3)) => {
+            _ = tokio::time::sleep_until(deadline) => {
                 break Some(ServerToClientMsg::Error("Timeouted".to_string()))
             }
         }
     };
 
-    {
-        clients.borrow_mut().remove_client(&username)
-    }
+    let id = client.id();
+    clients.with(|clients| clients.remove_client(&username, id));
     client.disconnect(message).await;
 }
+
+/// Sends [ServerToClientMsg::ServerTime] with the current time (milliseconds since the UNIX epoch)
+/// to all joined clients every `interval`.
+/// Clients whose message queue is full are skipped, so that one slow client cannot delay the others.
+pub async fn push_server_time<C: SharedClients>(clients: C, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    // The first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default();
+        for (_, sender) in clients.with(|clients| clients.get_all_clients()) {
+            let _ = sender.try_send(ServerToClientMsg::ServerTime(time));
+        }
+    }
+}
+
+/// Relays a file transfer handshake message to the user `to`.
+/// The server does not keep track of pending offers, it only forwards the messages between peers.
+async fn relay_file_message<C: SharedClients>(
+    client: &mut Client,
+    clients: &C,
+    username: &str,
+    to: &str,
+    message: ServerToClientMsg,
+) {
+    if to == username {
+        client
+            .send_message(ServerToClientMsg::Error(
+                "Cannot send a file to yourself".to_string(),
+            ))
+            .await
+            .unwrap_or_default();
+        return;
+    }
+    let senders = clients.with(|clients| clients.get_client(to));
+    for sender in &senders {
+        sender.send(message.clone()).await.unwrap_or_default();
+    }
+    if senders.is_empty() {
+        client
+            .send_message(ServerToClientMsg::Error(format!("User {to} does not exist")))
+            .await
+            .unwrap_or_default();
+    }
+}
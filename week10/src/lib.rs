@@ -14,11 +14,13 @@
 //! to spawn new asynchronous tasks.
 
 use crate::messages::ServerToClientMsg;
+use crate::rooms::RoomLimits;
 use client::*;
 use std::cell::RefCell;
 use std::future::Future;
 use std::pin::Pin;
 use std::rc::Rc;
+use std::time::Duration;
 use tokio::task::JoinHandle;
 
 /// Client handling
@@ -29,6 +31,8 @@ mod client;
 mod messages;
 /// Message reading
 mod reader;
+/// Chat rooms
+mod rooms;
 /// Message writing
 mod writer;
 
@@ -36,6 +40,16 @@ mod writer;
 struct ServerOpts {
     /// Maximum number of clients that can be connected to the server at once.
     max_clients: usize,
+    /// Message of the day sent to clients after they join, an empty message is not sent.
+    motd: &'static str,
+    /// Maximum number of chat rooms and members per room.
+    room_limits: RoomLimits,
+    /// How often the server sends `ServerTime` to all joined clients, `None` disables it.
+    server_time_interval: Option<Duration>,
+    /// Allows multiple clients (sessions) to join with the same username.
+    multi_session: bool,
+    /// Number of latest broadcasts replayed to clients after they join, 0 disables the history.
+    history_size: usize,
 }
 
 /// Representation of a running server
@@ -63,13 +77,28 @@ struct RunningServer {
 /// for more details. The details are the same as last week, with one exception described below.
 ///
 /// # Client connection
-/// When a client connects to the server, it should send a `Join` message.
-/// - If the client does not send a `Join` message within two seconds, the server should
-/// send an error "Timed out waiting for Join" and disconnect the client immediately.
+/// When a client connects to the server, it should send a `Hello` message with its protocol
+/// version, followed by a `Join` message.
+/// - If the client does not send a `Hello` message within two seconds, the server should
+///   send an error "Timed out waiting for Hello" and disconnect the client immediately.
+/// - If the protocol version does not match, the server should respond with
+///   `ServerError::UnsupportedVersion` and disconnect the client immediately.
+/// - If the client does not send a `Join` message within two seconds after `Hello`, the server
+///   should send an error "Timed out waiting for Join" and disconnect the client immediately.
 /// - If it sends anything else, the server should respond with an error "Unexpected message received"
 /// and disconnect the client immediately.
 /// - If the user sends a Join message (with a unique username), the server should respond with
-/// the `Welcome` message.
+/// the `Welcome` message, listing the features requested in `Hello` that it enabled.
+/// - If `compression` is enabled, all messages after `Welcome` are sent as compressed frames
+///   (in both directions) instead of JSON lines. Clients that do not request it are not affected.
+/// - If `opts.motd` is not empty, the server should send it in a `Motd` message right after
+///   `Welcome`.
+/// - If `opts.multi_session` is set, more clients can join with the same username. Each of them is
+///   a separate session of the user, DMs to the user are delivered to all its sessions and
+///   `UserList` reports the number of sessions of each user.
+/// - If `opts.history_size` is not zero, the server should send the latest (at most
+///   `opts.history_size`) broadcasts in a single `HistoryBatch` message after `Welcome` (and
+///   `Motd`), unless there were no broadcasts yet.
 ///
 /// Then it should start receiving requests from the client.
 /// - If the client ever sends the `Join` message again, the server should respond with an error
@@ -78,6 +107,9 @@ struct RunningServer {
 /// any message (through a DM or a broadcast) within that duration, the server should respond with
 /// an error "Timeouted" and disconnect the client immediately. This three second timer is refreshed
 /// everytime the client sends something or receives a DM/broadcast.
+/// - If `opts.server_time_interval` is set, the server should send a `ServerTime` message to every
+///   joined client with that interval, driven by a single timer task. These messages do not refresh
+///   the three second timer.
 ///
 /// # Maximum number of clients
 /// When a client connects and there are already `opts.max_clients` other clients connected, the
@@ -85,6 +117,12 @@ struct RunningServer {
 /// Note that if the server is full, the client should be disconnected even before it sends the
 /// `Join` message.
 ///
+/// # Rooms
+/// Clients can join named chat rooms, which are created on demand and removed once their last
+/// member leaves (or disconnects). At most `opts.room_limits.max_rooms` rooms can exist at once,
+/// and each room can have at most `opts.room_limits.max_members` members. When a limit would be
+/// exceeded, the server should respond with `ServerError::TooManyRooms` or `ServerError::RoomFull`.
+///
 /// # Graceful shutdown
 /// Your server should react to a message sent through the oneshot channel that you should create
 /// in `RunningServer`. When a message is received on this channel, the server should:
@@ -99,14 +137,21 @@ async fn run_server(opts: ServerOpts) -> anyhow::Result<RunningServer> {
     let port = listener.local_addr()?.port();
     let (tx, mut rx) = tokio::sync::oneshot::channel::<()>();
     let future = async move {
-        let clients = Rc::new(RefCell::new(Clients::new(opts.max_clients)));
+        let clients = Rc::new(RefCell::new(Clients::new(opts.max_clients, opts.room_limits, opts.multi_session, opts.history_size)));
         let mut handles: Vec<JoinHandle<()>> = Vec::with_capacity(opts.max_clients);
+        let server_time = opts
+            .server_time_interval
+            .map(|interval| tokio::task::spawn_local(push_server_time(clients.clone(), interval)));
+        let mut next_id = 0;
         loop {
             tokio::select! {
                 _ = &mut rx => break,
                 stream = listener.accept() => {
                     let client = match stream {
-                        Ok((stream, _)) => Client::new(stream),
+                        Ok((stream, _)) => {
+                            next_id += 1;
+                            Client::new(stream, next_id)
+                        }
                         Err(e) => {
                             eprintln!("Error: {e}");
                             continue;
@@ -128,13 +173,16 @@ async fn run_server(opts: ServerOpts) -> anyhow::Result<RunningServer> {
                     let handle = if handles.len() >= opts.max_clients {
                         tokio::task::spawn_local(client.disconnect(Some(ServerToClientMsg::Error("Server is full".to_string()))))
                     } else {
-                        tokio::task::spawn_local(handle_client(client, clients.clone()))
+                        tokio::task::spawn_local(handle_client(client, clients.clone(), opts.motd))
                     };
                     handles.push(handle);
                 }
             }
         }
 
+        if let Some(server_time) = server_time {
+            server_time.abort();
+        }
         clients.borrow_mut().clear();
 
         for handle in handles {
@@ -155,13 +203,18 @@ async fn run_server(opts: ServerOpts) -> anyhow::Result<RunningServer> {
 
 #[cfg(test)]
 mod tests {
-    use crate::messages::{ClientToServerMsg, ServerToClientMsg};
+    use crate::client::{handle_client_mt, Clients};
+    use crate::messages::{
+        ChatLine, ClientToServerMsg, Features, ServerError, ServerToClientMsg, PROTOCOL_VERSION,
+    };
     use crate::reader::MessageReader;
+    use crate::rooms::RoomLimits;
     use crate::writer::MessageWriter;
     use crate::{run_server, ServerOpts};
     use std::cell::{Cell, RefCell};
     use std::future::Future;
     use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
     use std::time::Duration;
     use tokio::io::AsyncWriteExt;
     use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
@@ -225,6 +278,7 @@ mod tests {
 
                 async move {
                     let mut client = spawner.client().await;
+                    let _ = client.try_send(hello()).await;
                     let _ = client
                         .try_send(ClientToServerMsg::Join {
                             name: format!("Client {client_id}"),
@@ -234,7 +288,7 @@ mod tests {
                         ServerToClientMsg::Error(_) => {
                             errors.set(errors.get() + 1);
                         }
-                        ServerToClientMsg::Welcome => {
+                        ServerToClientMsg::Welcome { .. } => {
                             successes.set(successes.get() + 1);
                             // Make sure that the client doesn't disconnect
                             joined_clients.borrow_mut().push(client);
@@ -286,6 +340,7 @@ mod tests {
     async fn join_timeout() {
         run_test(opts(2), |spawner| async move {
             let mut client = spawner.client().await;
+            client.send(hello()).await;
             sleep(3000).await;
             match client
                 .try_send(ClientToServerMsg::Join {
@@ -304,6 +359,84 @@ mod tests {
             .await;
     }
 
+    #[tokio::test]
+    async fn hello_timeout() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            sleep(3000).await;
+            if client.try_send(hello()).await.is_ok() {
+                client.expect_error("Timed out waiting for Hello").await;
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn hello_matching_version() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client
+                .send(ClientToServerMsg::Hello {
+                    protocol_version: PROTOCOL_VERSION,
+                    features: Features::default(),
+                })
+                .await;
+            client
+                .send(ClientToServerMsg::Join {
+                    name: "Foo".to_string(),
+                })
+                .await;
+            assert!(matches!(
+                client.recv().await,
+                ServerToClientMsg::Welcome { .. }
+            ));
+            client.ping().await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn hello_mismatching_version() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client
+                .send(ClientToServerMsg::Hello {
+                    protocol_version: PROTOCOL_VERSION + 1,
+                    features: Features::default(),
+                })
+                .await;
+            let error = ServerError::UnsupportedVersion {
+                requested: PROTOCOL_VERSION + 1,
+                supported: PROTOCOL_VERSION,
+            };
+            client.expect_error(&error.to_string()).await;
+            client.check_closed().await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn join_before_hello() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client
+                .send(ClientToServerMsg::Join {
+                    name: "Foo".to_string(),
+                })
+                .await;
+            client.expect_error("Unexpected message received").await;
+
+            Ok(())
+        })
+            .await;
+    }
+
     #[tokio::test]
     async fn duplicated_join() {
         run_test(opts(2), |spawner| async move {
@@ -348,6 +481,7 @@ mod tests {
             client.join("Foo").await;
 
             let mut client2 = spawner.client().await;
+            client2.send(hello()).await;
             client2
                 .send(ClientToServerMsg::Join {
                     name: "Foo".to_string(),
@@ -543,7 +677,7 @@ mod tests {
                 for _ in 0..count {
                     francesca.dm("Daina", "NO! Get your own!").await;
                     match francesca.recv().await {
-                        ServerToClientMsg::Message { from, message } => {
+                        ServerToClientMsg::Message { from, message, .. } => {
                             assert_eq!(from, "Diana");
                             assert_eq!(message, "Can I borrow your brush? Pleeeeeease :(((");
                         }
@@ -557,7 +691,7 @@ mod tests {
                 // error messages
                 for _ in 0..count {
                     match francesca.recv().await {
-                        ServerToClientMsg::Message { from, message } => {
+                        ServerToClientMsg::Message { from, message, .. } => {
                             assert_eq!(from, "Diana");
                             assert_eq!(message, "Can I borrow your brush? Pleeeeeease :(((");
                         }
@@ -607,7 +741,7 @@ mod tests {
                 for _ in 0..count {
                     francesca.send(ClientToServerMsg::Ping).await;
                     match francesca.recv().await {
-                        ServerToClientMsg::Message { from, message } => {
+                        ServerToClientMsg::Message { from, message, .. } => {
                             assert_eq!(from, "Diana");
                             assert_eq!(message, "Can I borrow your brush? Pleeeeeease :(((");
                         }
@@ -619,7 +753,7 @@ mod tests {
                 // pong messages
                 for _ in 0..count {
                     match francesca.recv().await {
-                        ServerToClientMsg::Message { from, message } => {
+                        ServerToClientMsg::Message { from, message, .. } => {
                             assert_eq!(from, "Diana");
                             assert_eq!(message, "Can I borrow your brush? Pleeeeeease :(((");
                         }
@@ -637,15 +771,48 @@ mod tests {
             .await;
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn dm_multi_thread() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let spawner = ClientSpawner {
+            port: listener.local_addr().unwrap().port(),
+        };
+        let clients = Arc::new(Mutex::new(Clients::new(2, opts(2).room_limits, false, 0)));
+
+        let server = tokio::spawn(async move {
+            let mut handles = vec![];
+            for id in 1..=2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let client = crate::client::Client::new(stream, id);
+                handles.push(tokio::spawn(handle_client_mt(client, clients.clone(), "")));
+            }
+            for handle in handles {
+                handle.await.unwrap();
+            }
+        });
+
+        let mut terrence = spawner.client().await;
+        terrence.join("Terrence").await;
+
+        let mut joe = spawner.client().await;
+        joe.join("Joe").await;
+
+        terrence.dm("Joe", "How you doin'").await;
+        joe.expect_message("Terrence", "How you doin'").await;
+        joe.dm("Terrence", "Fine").await;
+        terrence.expect_message("Joe", "Fine").await;
+
+        terrence.close().await;
+        joe.close().await;
+        server.await.unwrap();
+    }
+
     #[tokio::test]
     async fn broadcast_empty() {
         run_test(opts(2), |spawner| async move {
             let mut ji = spawner.client().await;
             ji.join("Ji").await;
-            ji.send(ClientToServerMsg::Broadcast {
-                message: "Haaaaaai!".to_string(),
-            })
-                .await;
+            assert_eq!(ji.broadcast("Haaaaaai!").await, 0);
             ji.ping().await;
 
             Ok(())
@@ -668,10 +835,7 @@ mod tests {
                 .collect();
             let users: Vec<Client> = futures_util::future::join_all(users).await;
 
-            niko.send(ClientToServerMsg::Broadcast {
-                message: "Borrow this!".to_string(),
-            })
-                .await;
+            assert_eq!(niko.broadcast("Borrow this!").await, 5);
             niko.ping().await;
 
             for mut user in users {
@@ -683,6 +847,645 @@ mod tests {
             .await;
     }
 
+    #[tokio::test]
+    async fn broadcast_ack() {
+        run_test(opts(10), |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+            let mut barbara = spawner.client().await;
+            barbara.join("Barbara").await;
+            // Clients that did not join yet do not receive broadcasts
+            let _anonymous = spawner.client().await;
+
+            assert_eq!(terrence.broadcast("Hi all").await, 2);
+            joe.expect_message("Terrence", "Hi all").await;
+            barbara.expect_message("Terrence", "Hi all").await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn motd() {
+        let opts = ServerOpts {
+            motd: "Be nice to each other",
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Foo").await;
+            match client.recv().await {
+                ServerToClientMsg::Motd(motd) => assert_eq!(motd, "Be nice to each other"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            client.ping().await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn server_time() {
+        let opts = ServerOpts {
+            server_time_interval: Some(Duration::from_millis(100)),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Terrence").await;
+
+            let mut times = vec![];
+            while times.len() < 2 {
+                match client.recv().await {
+                    ServerToClientMsg::ServerTime(time) => times.push(time),
+                    msg => panic!("Unexpected message {msg:?}"),
+                }
+            }
+            assert!(times[0] <= times[1]);
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn server_time_does_not_refresh_timeout() {
+        let opts = ServerOpts {
+            server_time_interval: Some(Duration::from_millis(500)),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Terrence").await;
+            loop {
+                match client.recv().await {
+                    ServerToClientMsg::ServerTime(_) => {}
+                    ServerToClientMsg::Error(error) => {
+                        assert_eq!(error, "Timeouted");
+                        break;
+                    }
+                    msg => panic!("Unexpected message {msg:?}"),
+                }
+            }
+            client.check_closed().await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn room_broadcast() {
+        run_test(opts(3), |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+            let mut barbara = spawner.client().await;
+            barbara.join("Barbara").await;
+
+            terrence.join_room("lobby").await;
+            joe.join_room("lobby").await;
+            barbara.join_room("kitchen").await;
+
+            terrence
+                .send(ClientToServerMsg::RoomBroadcast {
+                    room: "lobby".to_string(),
+                    message: "Hi".to_string(),
+                })
+                .await;
+            match joe.recv().await {
+                ServerToClientMsg::RoomMessage {
+                    room,
+                    from,
+                    message,
+                } => {
+                    assert_eq!(room, "lobby");
+                    assert_eq!(from, "Terrence");
+                    assert_eq!(message, "Hi");
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            barbara
+                .send(ClientToServerMsg::RoomBroadcast {
+                    room: "lobby".to_string(),
+                    message: "Hi".to_string(),
+                })
+                .await;
+            barbara
+                .expect_error("You are not a member of room lobby")
+                .await;
+
+            // Barbara did not receive the lobby message
+            barbara.ping().await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn room_full() {
+        let opts = ServerOpts {
+            room_limits: RoomLimits {
+                max_rooms: 10,
+                max_members: 2,
+            },
+            ..opts(3)
+        };
+        run_test(opts, |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+            let mut barbara = spawner.client().await;
+            barbara.join("Barbara").await;
+
+            terrence.join_room("lobby").await;
+            joe.join_room("lobby").await;
+            // Joining again does not take another slot
+            joe.join_room("lobby").await;
+            barbara
+                .send(ClientToServerMsg::JoinRoom {
+                    room: "lobby".to_string(),
+                })
+                .await;
+            barbara.expect_error("Room lobby is full").await;
+
+            terrence.leave_room("lobby").await;
+            barbara.join_room("lobby").await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn too_many_rooms() {
+        let opts = ServerOpts {
+            room_limits: RoomLimits {
+                max_rooms: 1,
+                max_members: 10,
+            },
+            ..opts(3)
+        };
+        run_test(opts, |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+
+            terrence.join_room("lobby").await;
+            joe.send(ClientToServerMsg::JoinRoom {
+                room: "kitchen".to_string(),
+            })
+            .await;
+            joe.expect_error("Too many rooms").await;
+            // Existing rooms can still be joined
+            joe.join_room("lobby").await;
+
+            // The lobby is removed once the last member leaves (or disconnects)
+            joe.leave_room("lobby").await;
+            terrence.close().await;
+            sleep(100).await;
+            joe.join_room("kitchen").await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn leave_room_not_member() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Terrence").await;
+            client
+                .send(ClientToServerMsg::LeaveRoom {
+                    room: "lobby".to_string(),
+                })
+                .await;
+            client
+                .expect_error("You are not a member of room lobby")
+                .await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn file_offer_accept() {
+        run_test(opts(2), |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+
+            terrence
+                .send(ClientToServerMsg::FileOffer {
+                    to: "Joe".to_string(),
+                    name: "notes.txt".to_string(),
+                    size: 1024,
+                })
+                .await;
+            match joe.recv().await {
+                ServerToClientMsg::IncomingFile { from, name, size } => {
+                    assert_eq!(from, "Terrence");
+                    assert_eq!(name, "notes.txt");
+                    assert_eq!(size, 1024);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            joe.send(ClientToServerMsg::FileAccept {
+                from: "Terrence".to_string(),
+            })
+            .await;
+            match terrence.recv().await {
+                ServerToClientMsg::FileAccepted { by } => assert_eq!(by, "Joe"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn file_offer_reject() {
+        run_test(opts(2), |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+
+            terrence
+                .send(ClientToServerMsg::FileOffer {
+                    to: "Fiona".to_string(),
+                    name: "notes.txt".to_string(),
+                    size: 1024,
+                })
+                .await;
+            terrence.expect_error("User Fiona does not exist").await;
+
+            joe.send(ClientToServerMsg::FileReject {
+                from: "Terrence".to_string(),
+            })
+            .await;
+            match terrence.recv().await {
+                ServerToClientMsg::FileRejected { by } => assert_eq!(by, "Joe"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn leave() {
+        run_test(opts(3), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Terrence").await;
+
+            let mut client2 = spawner.client().await;
+            client2.join("Joe").await;
+            client2.send(ClientToServerMsg::Leave).await;
+            client2.check_closed().await;
+
+            match client.recv().await {
+                ServerToClientMsg::UserLeft { name } => assert_eq!(name, "Joe"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            assert_eq!(client.list_users().await, vec!["Terrence".to_string()]);
+
+            // The username can be used again
+            let mut client3 = spawner.client().await;
+            client3.join("Joe").await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn history_batch() {
+        let opts = ServerOpts {
+            history_size: 3,
+            ..opts(4)
+        };
+        run_test(opts, |spawner| async move {
+            let mut alice = spawner.client().await;
+            alice.join("Alice").await;
+            let mut bob = spawner.client().await;
+            bob.join("Bob").await;
+
+            for message in ["First", "Second", "Third", "Fourth"] {
+                alice.broadcast(message).await;
+                bob.expect_message("Alice", message).await;
+            }
+            bob.broadcast("Fifth").await;
+            alice.expect_message("Bob", "Fifth").await;
+
+            let mut carol = spawner.client().await;
+            carol.join("Carol").await;
+            let line = |from: &str, message: &str| ChatLine {
+                from: from.to_string(),
+                message: message.to_string(),
+            };
+            match carol.recv().await {
+                ServerToClientMsg::HistoryBatch(lines) => assert_eq!(
+                    lines,
+                    vec![
+                        line("Alice", "Third"),
+                        line("Alice", "Fourth"),
+                        line("Bob", "Fifth")
+                    ]
+                ),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            // Later broadcasts are delivered one by one
+            alice.broadcast("Sixth").await;
+            carol.expect_message("Alice", "Sixth").await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn compression_negotiation() {
+        run_test(opts(3), |spawner| async move {
+            let mut alice = spawner.client().await;
+            assert!(alice.join_compressed("Alice").await.compression);
+            let mut bob = spawner.client().await;
+            assert!(bob.join_compressed("Bob").await.compression);
+
+            // Much larger than the line buffer of the reader, it only fits as a compressed frame
+            let large = "All work and no play makes Jack a dull boy. ".repeat(1000);
+            assert_eq!(alice.broadcast(&large).await, 1);
+            bob.expect_message("Alice", &large).await;
+
+            // A client without compression still talks to the others in JSON lines
+            let mut carol = spawner.client().await;
+            carol.join("Carol").await;
+            assert_eq!(carol.broadcast("Hi").await, 2);
+            alice.expect_message("Carol", "Hi").await;
+            bob.expect_message("Carol", "Hi").await;
+            bob.ping().await;
+            carol.ping().await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn multi_session() {
+        let opts = ServerOpts {
+            multi_session: true,
+            ..opts(4)
+        };
+        run_test(opts, |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut terrence2 = spawner.client().await;
+            terrence2.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+
+            assert_eq!(
+                joe.user_sessions().await,
+                vec![("Joe".to_string(), 1), ("Terrence".to_string(), 2)]
+            );
+
+            // A DM is delivered to all sessions of the user
+            joe.dm("Terrence", "Hi").await;
+            terrence.expect_message("Joe", "Hi").await;
+            terrence2.expect_message("Joe", "Hi").await;
+
+            drop(terrence2);
+            sleep(100).await;
+            assert_eq!(
+                joe.user_sessions().await,
+                vec![("Joe".to_string(), 1), ("Terrence".to_string(), 1)]
+            );
+            terrence.ping().await;
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn multi_session_leave() {
+        let opts = ServerOpts {
+            multi_session: true,
+            ..opts(4)
+        };
+        run_test(opts, |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut terrence2 = spawner.client().await;
+            terrence2.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+
+            // The user stays joined through its other session, so nobody is notified
+            terrence2.send(ClientToServerMsg::Leave).await;
+            terrence2.check_closed().await;
+            assert_eq!(
+                joe.list_users().await,
+                vec!["Joe".to_string(), "Terrence".to_string()]
+            );
+            terrence.ping().await;
+
+            terrence.send(ClientToServerMsg::Leave).await;
+            terrence.check_closed().await;
+            match joe.recv().await {
+                ServerToClientMsg::UserLeft { name } => assert_eq!(name, "Terrence"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            assert_eq!(joe.list_users().await, vec!["Joe".to_string()]);
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn who_am_i() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Terrence").await;
+            let mut client2 = spawner.client().await;
+            client2.join("Joe").await;
+
+            client.send(ClientToServerMsg::WhoAmI).await;
+            let id = match client.recv().await {
+                ServerToClientMsg::YouAre { name, id } => {
+                    assert_eq!(name, "Terrence");
+                    id
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            };
+            client2.send(ClientToServerMsg::WhoAmI).await;
+            match client2.recv().await {
+                ServerToClientMsg::YouAre { name, id: id2 } => {
+                    assert_eq!(name, "Joe");
+                    assert_ne!(id, id2);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn list_users_page() {
+        run_test(opts(10), |spawner| async move {
+            let mut clients = vec![];
+            for name in ["Eve", "Bob", "Dan", "Alice", "Carol"] {
+                let mut client = spawner.client().await;
+                client.join(name).await;
+                clients.push(client);
+            }
+            let client = &mut clients[0];
+
+            let pages = [
+                (0, 2, vec!["Alice", "Bob"]),
+                (2, 2, vec!["Carol", "Dan"]),
+                (4, 2, vec!["Eve"]),
+                (5, 2, vec![]),
+                (100, 2, vec![]),
+                (0, 0, vec![]),
+                (1, usize::MAX, vec!["Bob", "Carol", "Dan", "Eve"]),
+            ];
+            for (offset, limit, expected) in pages {
+                let (users, total) = client.users_page(offset, limit).await;
+                assert_eq!(users, expected, "offset {offset}, limit {limit}");
+                assert_eq!(total, 5);
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn message_sequence_numbers() {
+        run_test(opts(3), |spawner| async move {
+            let mut alice = spawner.client().await;
+            alice.join("Alice").await;
+            let mut bob = spawner.client().await;
+            bob.join("Bob").await;
+            let mut carol = spawner.client().await;
+            carol.join("Carol").await;
+
+            assert_eq!(alice.broadcast("First").await, 2);
+            assert_eq!(alice.broadcast("Second").await, 2);
+            carol.dm("Bob", "Third").await;
+            let mut seqs = vec![];
+            for expected in ["First", "Second", "Third"] {
+                match bob.recv().await {
+                    ServerToClientMsg::Message { message, seq, .. } => {
+                        assert_eq!(message, expected);
+                        seqs.push(seq);
+                    }
+                    msg => panic!("Unexpected message {msg:?}"),
+                }
+            }
+            assert_eq!(seqs, vec![1, 2, 3]);
+
+            // Each recipient has its own sequence
+            bob.dm("Alice", "Hi").await;
+            match alice.recv().await {
+                ServerToClientMsg::Message { seq, .. } => assert_eq!(seq, 1),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn server_stats() {
+        run_test(opts(3), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Terrence").await;
+            let mut client2 = spawner.client().await;
+            client2.join("Joe").await;
+
+            client.dm("Joe", "Hi").await;
+            client2.expect_message("Terrence", "Hi").await;
+            client.dm("Bob", "Hi").await;
+            client.expect_error("User Bob does not exist").await;
+            assert_eq!(client2.broadcast("Hello").await, 1);
+            client.expect_message("Joe", "Hello").await;
+
+            sleep(1100).await;
+            client.send(ClientToServerMsg::ServerStats).await;
+            match client.recv().await {
+                ServerToClientMsg::Stats {
+                    connected,
+                    uptime_secs,
+                    messages_routed,
+                } => {
+                    assert_eq!(connected, 2);
+                    assert!(uptime_secs >= 1);
+                    assert_eq!(messages_routed, 2);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn typing_rate_limit() {
+        run_test(opts(2), |spawner| async move {
+            let mut terrence = spawner.client().await;
+            terrence.join("Terrence").await;
+            let mut joe = spawner.client().await;
+            joe.join("Joe").await;
+
+            let typing = || ClientToServerMsg::Typing {
+                to: "Joe".to_string(),
+            };
+            terrence.send(typing()).await;
+            terrence.send(typing()).await;
+            terrence.dm("Joe", "Hi").await;
+            match joe.recv().await {
+                ServerToClientMsg::PeerTyping { from } => assert_eq!(from, "Terrence"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            // The second indicator was dropped, so the DM comes right after the first one
+            joe.expect_message("Terrence", "Hi").await;
+
+            sleep(1100).await;
+            terrence.send(typing()).await;
+            match joe.recv().await {
+                ServerToClientMsg::PeerTyping { from } => assert_eq!(from, "Terrence"),
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+
+            Ok(())
+        })
+            .await;
+    }
+
     #[tokio::test]
     async fn message_timeout() {
         run_test(opts(2), |spawner| async move {
@@ -808,12 +1611,33 @@ mod tests {
 
     impl Client {
         async fn join(&mut self, name: &str) {
+            self.send(hello()).await;
             self.send(ClientToServerMsg::Join {
                 name: name.to_string(),
             })
                 .await;
             let msg = self.recv().await;
-            assert!(matches!(msg, ServerToClientMsg::Welcome));
+            assert!(matches!(msg, ServerToClientMsg::Welcome { .. }));
+        }
+
+        /// Joins with compression requested, switching to compressed frames if the server enabled
+        /// it.
+        async fn join_compressed(&mut self, name: &str) -> Features {
+            self.send(ClientToServerMsg::Hello {
+                protocol_version: PROTOCOL_VERSION,
+                features: Features { compression: true },
+            })
+            .await;
+            self.send(ClientToServerMsg::Join {
+                name: name.to_string(),
+            })
+            .await;
+            let ServerToClientMsg::Welcome { features } = self.recv().await else {
+                panic!("did not receive welcome message");
+            };
+            self.reader.set_compressed(features.compression);
+            self.writer.set_compressed(features.compression);
+            features
         }
 
         async fn ping(&mut self) {
@@ -823,6 +1647,15 @@ mod tests {
         }
 
         async fn list_users(&mut self) -> Vec<String> {
+            self.user_sessions()
+                .await
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        }
+
+        /// Returns the sorted usernames together with their number of sessions
+        async fn user_sessions(&mut self) -> Vec<(String, usize)> {
             self.send(ClientToServerMsg::ListUsers).await;
             let msg = self.recv().await;
             match msg {
@@ -836,6 +1669,20 @@ mod tests {
             }
         }
 
+        async fn users_page(&mut self, offset: usize, limit: usize) -> (Vec<String>, usize) {
+            self.send(ClientToServerMsg::ListUsersPage { offset, limit })
+                .await;
+            match self.recv().await {
+                ServerToClientMsg::UserListPage { users, total } => (
+                    users.into_iter().map(|(name, _)| name).collect(),
+                    total,
+                ),
+                msg => {
+                    panic!("Unexpected response {msg:?}");
+                }
+            }
+        }
+
         async fn dm(&mut self, to: &str, message: &str) {
             self.send(ClientToServerMsg::SendDM {
                 to: to.to_string(),
@@ -844,10 +1691,44 @@ mod tests {
                 .await;
         }
 
+        /// Broadcasts a message and returns the number of recipients from the acknowledgement
+        async fn broadcast(&mut self, message: &str) -> usize {
+            self.send(ClientToServerMsg::Broadcast {
+                message: message.to_string(),
+            })
+                .await;
+            match self.recv().await {
+                ServerToClientMsg::BroadcastAck { delivered } => delivered,
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn join_room(&mut self, room: &str) {
+            self.send(ClientToServerMsg::JoinRoom {
+                room: room.to_string(),
+            })
+            .await;
+            match self.recv().await {
+                ServerToClientMsg::RoomJoined { room: joined } => assert_eq!(joined, room),
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn leave_room(&mut self, room: &str) {
+            self.send(ClientToServerMsg::LeaveRoom {
+                room: room.to_string(),
+            })
+            .await;
+            match self.recv().await {
+                ServerToClientMsg::RoomLeft { room: left } => assert_eq!(left, room),
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
         async fn expect_message(&mut self, expected_from: &str, expected_message: &str) {
             let msg = self.recv().await;
             match msg {
-                ServerToClientMsg::Message { from, message } => {
+                ServerToClientMsg::Message { from, message, .. } => {
                     assert_eq!(from, expected_from);
                     assert_eq!(message, expected_message);
                 }
@@ -915,7 +1796,24 @@ mod tests {
         tokio::time::sleep(Duration::from_millis(duration_ms)).await;
     }
 
+    fn hello() -> ClientToServerMsg {
+        ClientToServerMsg::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            features: Features::default(),
+        }
+    }
+
     fn opts(max_clients: usize) -> ServerOpts {
-        ServerOpts { max_clients }
+        ServerOpts {
+            max_clients,
+            motd: "",
+            room_limits: RoomLimits {
+                max_rooms: 10,
+                max_members: 10,
+            },
+            server_time_interval: None,
+            multi_session: false,
+            history_size: 0,
+        }
     }
 }
@@ -1,8 +1,34 @@
+use std::fmt::{Display, Formatter};
+
+/// Version of the protocol spoken by the server, see [ClientToServerMsg::Hello].
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features of the protocol that are negotiated for each connection, see
+/// [ClientToServerMsg::Hello].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Features {
+    /// Messages are sent as gzip-compressed frames instead of JSON lines, see
+    /// [crate::writer::MessageWriter::set_compressed].
+    #[serde(default)]
+    pub compression: bool,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum ClientToServerMsg {
-    /// This is the first message in the communication, which should be sent by the client.
+    /// This is the first message in the communication, it has to precede [ClientToServerMsg::Join].
+    /// When `protocol_version` differs from [PROTOCOL_VERSION], the server should respond with
+    /// [ServerError::UnsupportedVersion] and disconnect the client.
+    /// `features` are the optional features supported by the client, the server enables those it
+    /// supports as well and announces them in [ServerToClientMsg::Welcome].
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        features: Features,
+    },
+    /// This message should be sent by the client right after [ClientToServerMsg::Hello].
     /// When some other client with the same name already exists, the server should respond
-    /// with an error "Username already taken" and disconnect the new client.
+    /// with an error "Username already taken" and disconnect the new client, unless it allows
+    /// multiple sessions with the same name.
     Join { name: String },
     /// This message checks that the connection is OK.
     /// The server should respond with [ServerToClientMsg::Pong].
@@ -10,26 +36,175 @@ pub enum ClientToServerMsg {
     /// Send a request to list the usernames of users currently connected to the server.
     /// The order of the usernames is not important.
     ListUsers,
+    /// Send a request to list at most `limit` users, starting at the `offset`-th user in the list
+    /// sorted by username.
+    /// The server should respond with [ServerToClientMsg::UserListPage].
+    ListUsersPage { offset: usize, limit: usize },
     /// Sends a direct message to the user with the given name (`to`).
     /// If the user does not exist, the server responds with an error "User <to> does not exist".
     /// If the client tries to send a message to themselves, the server responds with an error
     /// "Cannot send a DM to yourself".
     SendDM { to: String, message: String },
     /// Sends a message to all currently connected users (except for the sender of the broadcast).
+    /// The server should respond with [ServerToClientMsg::BroadcastAck].
     Broadcast { message: String },
+    /// Offers a file with the given `name` and `size` (in bytes) to the user `to`.
+    /// The server relays the offer to the recipient as [ServerToClientMsg::IncomingFile],
+    /// the file contents are not transferred through this message.
+    /// If the user does not exist, the server responds with an error "User <to> does not exist".
+    /// If the client tries to offer a file to themselves, the server responds with an error
+    /// "Cannot send a file to yourself".
+    FileOffer { to: String, name: String, size: u64 },
+    /// Accepts a file offered by the user `from`.
+    /// The server relays it to the offering user as [ServerToClientMsg::FileAccepted].
+    FileAccept { from: String },
+    /// Rejects a file offered by the user `from`.
+    /// The server relays it to the offering user as [ServerToClientMsg::FileRejected].
+    FileReject { from: String },
+    /// Notifies the user `to` that the client is typing, the server relays it as
+    /// [ServerToClientMsg::PeerTyping].
+    /// The server relays at most one typing indicator per second from each client, the others
+    /// (and indicators sent to unknown users or to the client itself) are silently dropped.
+    Typing { to: String },
+    /// Joins the chat room with the given name, creating it if it does not exist.
+    /// The server responds with [ServerToClientMsg::RoomJoined], or with
+    /// [ServerError::RoomFull]/[ServerError::TooManyRooms] if a room limit would be exceeded.
+    JoinRoom { room: String },
+    /// Leaves the chat room with the given name. A room is removed once its last member leaves.
+    /// The server responds with [ServerToClientMsg::RoomLeft], or with [ServerError::NotInRoom].
+    LeaveRoom { room: String },
+    /// Sends a message to all other members of the given room as [ServerToClientMsg::RoomMessage].
+    /// If the client is not a member of the room, the server responds with [ServerError::NotInRoom].
+    RoomBroadcast { room: String, message: String },
+    /// Leaves the chat gracefully. The server removes the user, notifies the remaining users
+    /// with [ServerToClientMsg::UserLeft] and closes the connection.
+    Leave,
+    /// Asks the server for the client's own name and connection ID.
+    /// The server should respond with [ServerToClientMsg::YouAre].
+    WhoAmI,
+    /// Asks the server how busy it is.
+    /// The server should respond with [ServerToClientMsg::Stats].
+    ServerStats,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum ServerToClientMsg {
-    /// Response to [ClientToServerMsg::Join].
-    Welcome,
+    /// Response to [ClientToServerMsg::Join] with the features enabled for the connection.
+    /// The enabled features apply to all messages after this one, in both directions.
+    Welcome { features: Features },
+    /// Message of the day, sent right after [ServerToClientMsg::Welcome] if the server has one
+    /// configured.
+    Motd(String),
+    /// The latest broadcasts (oldest first), sent right after [ServerToClientMsg::Welcome] (and
+    /// [ServerToClientMsg::Motd]) if the server keeps a history of broadcasts and it is not empty.
+    HistoryBatch(Vec<ChatLine>),
     /// Response to [ClientToServerMsg::Ping].
     Pong,
-    /// Response to [ClientToServerMsg::ListUsers].
-    UserList { users: Vec<String> },
+    /// Response to [ClientToServerMsg::ListUsers] with the usernames and the number of sessions
+    /// (connections) of each user.
+    UserList { users: Vec<(String, usize)> },
+    /// Response to [ClientToServerMsg::ListUsersPage] with the requested page of the users (and
+    /// their numbers of sessions) sorted by username, and the `total` number of users.
+    UserListPage {
+        users: Vec<(String, usize)>,
+        total: usize,
+    },
     /// This message is sent by the server to a client that should receive a message
     /// (that was sent either by [ClientToServerMsg::SendDM] or [ClientToServerMsg::Broadcast]).
-    Message { from: String, message: String },
+    /// `seq` numbers the messages delivered to the receiving client, starting from 1 and increasing
+    /// by one with each message, so that the client can detect dropped or reordered messages.
+    Message {
+        from: String,
+        message: String,
+        seq: u64,
+    },
+    /// Response to [ClientToServerMsg::Broadcast] with the number of users that received the
+    /// message (the sender is not counted).
+    BroadcastAck { delivered: usize },
+    /// This message is sent by the server to a client that was offered a file by
+    /// [ClientToServerMsg::FileOffer].
+    IncomingFile { from: String, name: String, size: u64 },
+    /// This message is sent by the server to a client whose file offer was accepted
+    /// by the user `by` with [ClientToServerMsg::FileAccept].
+    FileAccepted { by: String },
+    /// This message is sent by the server to a client whose file offer was rejected
+    /// by the user `by` with [ClientToServerMsg::FileReject].
+    FileRejected { by: String },
+    /// This message is sent by the server to a client that is being typed to by the user `from`
+    /// (see [ClientToServerMsg::Typing]).
+    PeerTyping { from: String },
+    /// Response to [ClientToServerMsg::JoinRoom].
+    RoomJoined { room: String },
+    /// Response to [ClientToServerMsg::LeaveRoom].
+    RoomLeft { room: String },
+    /// This message is sent by the server to members of a room that received a message
+    /// (that was sent by [ClientToServerMsg::RoomBroadcast]).
+    RoomMessage {
+        room: String,
+        from: String,
+        message: String,
+    },
+    /// This message is sent by the server to all remaining users when the user `name` leaves
+    /// with [ClientToServerMsg::Leave].
+    UserLeft { name: String },
+    /// Response to [ClientToServerMsg::WhoAmI] with the name the client joined with and the ID
+    /// the server assigned to its connection.
+    YouAre { name: String, id: u64 },
+    /// Response to [ClientToServerMsg::ServerStats] with the number of connected (joined) clients,
+    /// the number of seconds since the server started and the number of messages (DMs, broadcasts
+    /// and room messages) the server delivered to their recipients.
+    Stats {
+        connected: usize,
+        uptime_secs: u64,
+        messages_routed: u64,
+    },
+    /// Current time of the server in milliseconds since the UNIX epoch.
+    /// It is sent periodically to all joined clients if the server is configured to do so, so that
+    /// the clients can detect a dead server.
+    ServerTime(u64),
     /// This message is returned by the server when an error occurs.
     Error(String),
 }
+
+/// Message broadcast by the user `from`, replayed in [ServerToClientMsg::HistoryBatch].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatLine {
+    pub from: String,
+    pub message: String,
+}
+
+/// Errors reported to the client through [ServerToClientMsg::Error].
+#[derive(Debug, Eq, PartialEq)]
+pub enum ServerError {
+    /// The client speaks a different version of the protocol than the server.
+    UnsupportedVersion { requested: u32, supported: u32 },
+    /// The room already has the maximum number of members.
+    RoomFull { room: String },
+    /// The maximum number of rooms already exists, so no new room can be created.
+    TooManyRooms,
+    /// The client is not a member of the room.
+    NotInRoom { room: String },
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::UnsupportedVersion {
+                requested,
+                supported,
+            } => write!(
+                f,
+                "Unsupported protocol version {requested} (supported: {supported})"
+            ),
+            ServerError::RoomFull { room } => write!(f, "Room {room} is full"),
+            ServerError::TooManyRooms => write!(f, "Too many rooms"),
+            ServerError::NotInRoom { room } => write!(f, "You are not a member of room {room}"),
+        }
+    }
+}
+
+impl From<ServerError> for ServerToClientMsg {
+    fn from(error: ServerError) -> Self {
+        ServerToClientMsg::Error(error.to_string())
+    }
+}
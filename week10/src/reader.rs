@@ -1,11 +1,19 @@
+use flate2::read::GzDecoder;
 use serde::de::DeserializeOwned;
+use std::io::{ErrorKind, Read};
 use std::marker::PhantomData;
 use tokio::io::{AsyncRead, AsyncReadExt};
 
+/// Maximum size of a compressed frame.
+const MAX_FRAME_SIZE: usize = 64 * 1024;
+/// Maximum size of a decompressed message, protects against decompression bombs.
+const MAX_DECOMPRESSED_SIZE: u64 = 1024 * 1024;
+
 pub struct MessageReader<T, R> {
     buffer: Vec<u8>,
     loaded: usize,
     client: R,
+    compressed: bool,
     _phantom: PhantomData<T>,
 }
 
@@ -15,22 +23,22 @@ impl<T: DeserializeOwned, R: AsyncRead + Unpin> MessageReader<T, R> {
             buffer: vec![0; 1024],
             loaded: 0,
             client,
+            compressed: false,
             _phantom: Default::default(),
         }
     }
 
+    /// Switches between JSON lines and compressed frames (see
+    /// [`crate::writer::MessageWriter::set_compressed`]) for the following messages.
+    /// Data that was already received but not returned yet is read in the new format.
+    pub fn set_compressed(&mut self, compressed: bool) {
+        self.compressed = compressed;
+    }
+
     pub async fn recv(&mut self) -> Option<std::io::Result<T>> {
         loop {
-            if let Some(position) = self.buffer[..self.loaded].iter().position(|c| *c == b'\n') {
-                let msg = &self.buffer[..position];
-                let msg: T = match serde_json::from_slice(msg) {
-                    Ok(msg) => msg,
-                    Err(error) => return Some(Err(error.into())),
-                };
-                self.buffer.copy_within(position + 1.., 0);
-
-                self.loaded -= position + 1;
-                return Some(Ok(msg));
+            if let Some(msg) = self.take_message() {
+                return Some(msg);
             }
 
             assert!(self.loaded < self.buffer.len());
@@ -45,4 +53,52 @@ impl<T: DeserializeOwned, R: AsyncRead + Unpin> MessageReader<T, R> {
         }
         None
     }
+
+    /// Removes the first message from the loaded data and deserializes it.
+    /// Returns `None` if the whole message has not been loaded yet.
+    fn take_message(&mut self) -> Option<std::io::Result<T>> {
+        let (msg, length) = if self.compressed {
+            let header = self.buffer[..self.loaded].get(..4)?;
+            let size = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+            if size > MAX_FRAME_SIZE {
+                return Some(Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Frame too large ({size} bytes)"),
+                )));
+            }
+            let length = 4 + size;
+            if self.buffer.len() < length {
+                self.buffer.resize(length, 0);
+            }
+            if self.loaded < length {
+                return None;
+            }
+            let msg = decompress(&self.buffer[4..length])
+                .and_then(|msg| serde_json::from_slice(&msg).map_err(Into::into));
+            (msg, length)
+        } else {
+            let position = self.buffer[..self.loaded]
+                .iter()
+                .position(|c| *c == b'\n')?;
+            let msg = serde_json::from_slice(&self.buffer[..position]).map_err(Into::into);
+            (msg, position + 1)
+        };
+        self.buffer.copy_within(length..self.loaded, 0);
+        self.loaded -= length;
+        Some(msg)
+    }
+}
+
+fn decompress(frame: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    GzDecoder::new(frame)
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "Decompressed message too large",
+        ));
+    }
+    Ok(decompressed)
 }
@@ -0,0 +1,80 @@
+use crate::messages::ServerError;
+use std::collections::{HashMap, HashSet};
+
+/// Limits of the chat rooms on the server.
+#[derive(Copy, Clone, Debug)]
+pub struct RoomLimits {
+    /// Maximum number of rooms that can exist at once.
+    pub max_rooms: usize,
+    /// Maximum number of members of a single room.
+    pub max_members: usize,
+}
+
+/// Chat rooms and their members.
+/// A room is created when the first member joins it, and it is removed once its last member leaves.
+pub struct Rooms {
+    rooms: HashMap<String, HashSet<String>>,
+    limits: RoomLimits,
+}
+
+impl Rooms {
+    pub fn new(limits: RoomLimits) -> Self {
+        Self {
+            rooms: HashMap::new(),
+            limits,
+        }
+    }
+
+    /// Adds `username` to the `room`, creating the room if it does not exist yet.
+    /// Joining a room that the user is already a member of does nothing.
+    pub fn join(&mut self, room: &str, username: &str) -> Result<(), ServerError> {
+        if !self.rooms.contains_key(room) && self.rooms.len() >= self.limits.max_rooms {
+            return Err(ServerError::TooManyRooms);
+        }
+
+        let members = self.rooms.get(room);
+        let is_member = members.is_some_and(|members| members.contains(username));
+        if !is_member && members.map_or(0, HashSet::len) >= self.limits.max_members {
+            return Err(ServerError::RoomFull {
+                room: room.to_string(),
+            });
+        }
+        self.rooms
+            .entry(room.to_string())
+            .or_default()
+            .insert(username.to_string());
+        Ok(())
+    }
+
+    /// Removes `username` from the `room`, removing the room if it becomes empty.
+    /// Returns false if the user was not a member of the room.
+    pub fn leave(&mut self, room: &str, username: &str) -> bool {
+        let Some(members) = self.rooms.get_mut(room) else {
+            return false;
+        };
+        let removed = members.remove(username);
+        if members.is_empty() {
+            self.rooms.remove(room);
+        }
+        removed
+    }
+
+    /// Removes `username` from all rooms, removing the rooms that become empty.
+    pub fn leave_all(&mut self, username: &str) {
+        self.rooms.retain(|_, members| {
+            members.remove(username);
+            !members.is_empty()
+        });
+    }
+
+    /// Returns the members of the `room`, or `None` if the room does not exist.
+    pub fn members(&self, room: &str) -> Option<Vec<String>> {
+        self.rooms
+            .get(room)
+            .map(|members| members.iter().cloned().collect())
+    }
+
+    pub fn clear(&mut self) {
+        self.rooms.clear();
+    }
+}
@@ -1,9 +1,13 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use serde::Serialize;
+use std::io::Write;
 use std::marker::PhantomData;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 pub struct MessageWriter<T, W> {
     stream: W,
+    compressed: bool,
     _phantom: PhantomData<T>,
 }
 
@@ -11,14 +15,32 @@ impl<T: Serialize, W: AsyncWrite + Unpin> MessageWriter<T, W> {
     pub fn new(stream: W) -> Self {
         Self {
             stream,
+            compressed: false,
             _phantom: Default::default(),
         }
     }
 
+    /// Switches between JSON lines and compressed frames for the following messages.
+    /// A compressed frame is the length of the payload (`u32`, little endian) followed by the
+    /// gzip-compressed JSON of the message. The frames can only be read by a
+    /// [`crate::reader::MessageReader`] that is switched to compressed frames as well.
+    pub fn set_compressed(&mut self, compressed: bool) {
+        self.compressed = compressed;
+    }
+
     pub async fn send(&mut self, msg: T) -> anyhow::Result<()> {
         let serialized = serde_json::to_vec(&msg)?;
-        self.stream.write_all(&serialized).await?;
-        self.stream.write_all(b"\n").await?;
+        if self.compressed {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(&serialized)?;
+            let compressed = encoder.finish()?;
+            let size = u32::try_from(compressed.len())?;
+            self.stream.write_all(&size.to_le_bytes()).await?;
+            self.stream.write_all(&compressed).await?;
+        } else {
+            self.stream.write_all(&serialized).await?;
+            self.stream.write_all(b"\n").await?;
+        }
         self.stream.flush().await?;
         Ok(())
     }
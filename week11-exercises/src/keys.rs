@@ -0,0 +1,113 @@
+use crate::messages::Action;
+use crossterm::event::KeyEventKind;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Tracks which action keys are currently held down, so that key-repeat events of a held key
+/// produce an action at most once per `interval`.
+///
+/// Releasing a key resets its state, so pressing it again produces an action immediately.
+pub struct HeldKeys {
+    interval: Duration,
+    /// Held actions and the time when they were last sent
+    held: HashMap<Action, Instant>,
+}
+
+impl HeldKeys {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            held: HashMap::new(),
+        }
+    }
+
+    /// Processes a key event of the given `kind` for `action` at time `now`.
+    /// Returns true if the action should be sent to the server.
+    pub fn update(&mut self, action: Action, kind: KeyEventKind, now: Instant) -> bool {
+        match kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => match self.held.get_mut(&action) {
+                Some(last_sent) if now.duration_since(*last_sent) < self.interval => false,
+                Some(last_sent) => {
+                    *last_sent = now;
+                    true
+                }
+                None => {
+                    self.held.insert(action, now);
+                    true
+                }
+            },
+            KeyEventKind::Release => {
+                self.held.remove(&action);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keys::HeldKeys;
+    use crate::messages::Action;
+    use crossterm::event::KeyEventKind;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn held_key_is_debounced() {
+        let mut keys = HeldKeys::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        // Key repeat events arrive every 30 ms while the key is held
+        let sent: Vec<u64> = (0..12)
+            .map(|i| i * 30)
+            .filter(|&ms| {
+                let kind = if ms == 0 {
+                    KeyEventKind::Press
+                } else {
+                    KeyEventKind::Repeat
+                };
+                keys.update(
+                    Action::MoveForward,
+                    kind,
+                    start + Duration::from_millis(ms),
+                )
+            })
+            .collect();
+        assert_eq!(sent, vec![0, 120, 240]);
+    }
+
+    #[test]
+    fn release_resets_key() {
+        let mut keys = HeldKeys::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        assert!(keys.update(Action::Fire, KeyEventKind::Press, start));
+        assert!(!keys.update(
+            Action::Fire,
+            KeyEventKind::Release,
+            start + Duration::from_millis(10)
+        ));
+        assert!(keys.update(
+            Action::Fire,
+            KeyEventKind::Press,
+            start + Duration::from_millis(20)
+        ));
+    }
+
+    #[test]
+    fn keys_are_independent() {
+        let mut keys = HeldKeys::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        assert!(keys.update(Action::MoveForward, KeyEventKind::Press, start));
+        assert!(keys.update(
+            Action::TurnLeft,
+            KeyEventKind::Press,
+            start + Duration::from_millis(10)
+        ));
+        assert!(!keys.update(
+            Action::MoveForward,
+            KeyEventKind::Repeat,
+            start + Duration::from_millis(50)
+        ));
+    }
+}
@@ -38,6 +38,8 @@
 //! 3) Read key events from the keyboard using the provided code, and map some keyboard keys
 //! to actions (and then actions to numbers). After an action is produced by the corresponding key,
 //! send the [`ClientToServerMsg::PerformAction`] message to the server.
+//!    The server answers with [`ServerToClientMsg::ActionResult`], which tells you whether the
+//!    action was performed (it can e.g. still be on cooldown).
 //!
 //! If you send an invalid action, the server will freeze your blob for a few seconds, and increase
 //! incoming damage by 100%.
@@ -56,19 +58,24 @@
 //!
 //! Bonus point if you can crash the server :)
 
-use crate::messages::{Action, ClientToServerMsg, ServerToClientMsg};
+use crate::keys::HeldKeys;
+use crate::messages::{Action, ActionOutcome, ClientToServerMsg, ServerError, ServerToClientMsg};
 use crate::reader::MessageReader;
 use crate::writer::MessageWriter;
 use anyhow::anyhow;
-use crossterm::event::{Event, EventStream, KeyCode};
+use crossterm::event::{
+    Event, EventStream, KeyCode, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
 use futures::StreamExt;
 use std::collections::HashMap;
 use std::io::Write;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::net::tcp::OwnedWriteHalf;
 use tokio::net::TcpStream;
 use tokio::{select, time};
 
+mod keys;
 mod messages;
 mod reader;
 mod writer;
@@ -86,13 +93,39 @@ macro_rules! output {
     };
 }
 
+/// Minimal delay between two actions sent for a key that is being held down.
+const KEY_REPEAT_INTERVAL: Duration = Duration::from_millis(100);
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> anyhow::Result<()> {
     // Enable raw mode so that input key events are not buffered
     crossterm::terminal::enable_raw_mode()?;
-    let result = run().await;
-    crossterm::terminal::disable_raw_mode()?;
-    result
+    let mut terminal = TerminalGuard { enhanced: false };
+    // Ask the terminal to report key releases, so that we know when a held key stops.
+    // A terminal that cannot be queried is treated as not supporting it.
+    if crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false) {
+        crossterm::execute!(
+            std::io::stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+        terminal.enhanced = true;
+    }
+    run().await
+}
+
+/// Restores the terminal when dropped, so that it does not stay in raw mode if the client fails.
+struct TerminalGuard {
+    /// Whether the keyboard enhancement flags were pushed and have to be popped
+    enhanced: bool,
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.enhanced {
+            let _ = crossterm::execute!(std::io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
 }
 
 async fn run() -> anyhow::Result<()> {
@@ -114,6 +147,7 @@ async fn run() -> anyhow::Result<()> {
     let mut keys = EventStream::new();
 
     let mut action_mapping: HashMap<Action, u8> = HashMap::new();
+    let mut held_keys = HeldKeys::new(KEY_REPEAT_INTERVAL);
     let mut interval = tokio::time::interval(Duration::from_secs(1));
 
     loop {
@@ -126,30 +160,100 @@ async fn run() -> anyhow::Result<()> {
                 Some(Ok(result)) => match result {
                     ServerToClientMsg::ActionMappingUpdate(mapping) => {
                         output!("Received action mapping update: {:?}", mapping);
-                        for (index, action) in mapping.into_iter().enumerate() {
-                            action_mapping.insert(action, index as u8);
-                        }
+                        action_mapping = decode_mapping(&mapping);
                     }
-                    ServerToClientMsg::Error(e) => {
-                        eprintln!("{e}");
-                        break;
+                    ServerToClientMsg::ActionResult { action, outcome } => {
+                        if outcome != ActionOutcome::Performed {
+                            output!("{:?}: {:?}", action, outcome);
+                        }
                     }
+                    ServerToClientMsg::Error(e) => match ServerError::classify(e) {
+                        ServerError::Fatal(e) => {
+                            eprintln!("{e}");
+                            break;
+                        }
+                        ServerError::Recoverable(e) => {
+                            output!("Server error: {}", e);
+                        }
+                    },
                 },
                 _ => break,
             },
             Some(Ok(Event::Key(event))) = keys.next() => {
                 let action = match event.code {
-                    KeyCode::Char('w') => action_mapping.get(&Action::MoveForward).unwrap(),
-                    KeyCode::Char('s') => action_mapping.get(&Action::MoveBackward).unwrap(),
-                    KeyCode::Char('a') => action_mapping.get(&Action::TurnLeft).unwrap(),
-                    KeyCode::Char('d') => action_mapping.get(&Action::TurnRight).unwrap(),
-                    KeyCode::Char('e') => action_mapping.get(&Action::Fire).unwrap(),
-                    KeyCode::Char('q') => action_mapping.get(&Action::Shield).unwrap(),
+                    KeyCode::Char('w') => Action::MoveForward,
+                    KeyCode::Char('s') => Action::MoveBackward,
+                    KeyCode::Char('a') => Action::TurnLeft,
+                    KeyCode::Char('d') => Action::TurnRight,
+                    KeyCode::Char('e') => Action::Fire,
+                    KeyCode::Char('q') => Action::Shield,
+                    KeyCode::Char('b') => Action::Boost,
+                    KeyCode::Char('m') => Action::DropMine,
                     _ => continue,
                 };
-                tx.send(ClientToServerMsg::PerformAction(*action)).await?;
+                if !held_keys.update(action, event.kind, Instant::now()) {
+                    continue;
+                }
+                // The server does not have to map every action
+                let Some(index) = action_mapping.get(&action) else {
+                    continue;
+                };
+                tx.send(ClientToServerMsg::PerformAction(*index)).await?;
             }
         }
     }
     Ok(())
 }
+
+/// Maps each action from the server's mapping to the number that invokes it.
+/// Indices that do not fit into the protocol's `u8` are ignored.
+fn decode_mapping(mapping: &[Action]) -> HashMap<Action, u8> {
+    mapping
+        .iter()
+        .enumerate()
+        .filter_map(|(index, action)| Some((*action, u8::try_from(index).ok()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode_mapping;
+    use crate::messages::{Action, ServerError, ServerToClientMsg};
+
+    #[test]
+    fn mapping_with_new_actions() {
+        let msg: ServerToClientMsg = serde_json::from_str(
+            r#"{"ActionMappingUpdate":["Fire","Boost","TurnLeft","DropMine","Invalid"]}"#,
+        )
+        .unwrap();
+        let ServerToClientMsg::ActionMappingUpdate(mapping) = msg else {
+            panic!("Unexpected message {msg:?}");
+        };
+
+        let mapping = decode_mapping(&mapping);
+        assert_eq!(mapping.len(), 5);
+        assert_eq!(mapping[&Action::Boost], 1);
+        assert_eq!(mapping[&Action::DropMine], 3);
+        assert_eq!(mapping.get(&Action::Shield), None);
+    }
+
+    #[test]
+    fn error_classification() {
+        assert_eq!(
+            ServerError::classify("Username already taken".to_string()),
+            ServerError::Fatal("Username already taken".to_string())
+        );
+        assert_eq!(
+            ServerError::classify("Timed out waiting for Join".to_string()),
+            ServerError::Fatal("Timed out waiting for Join".to_string())
+        );
+        assert_eq!(
+            ServerError::classify("User Joe does not exist".to_string()),
+            ServerError::Recoverable("User Joe does not exist".to_string())
+        );
+        assert_eq!(
+            ServerError::classify("Something unexpected".to_string()),
+            ServerError::Recoverable("Something unexpected".to_string())
+        );
+    }
+}
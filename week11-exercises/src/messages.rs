@@ -34,6 +34,12 @@ pub enum Action {
     /// Shoot a message in the direction of your blob.
     /// Shooting has a short cooldown.
     Fire,
+    /// Temporarily increase the speed of your blob.
+    /// Boosting has a long cooldown.
+    Boost,
+    /// Drop a mine at the current position of your blob.
+    /// Only a limited number of mines can be placed at once.
+    DropMine,
     /// If you send this action to the server, it will freeze the blob and increase the damage it
     /// receives.
     Invalid,
@@ -43,8 +49,54 @@ pub enum Action {
 pub enum ServerToClientMsg {
     /// Updates the mapping of actions to numbers.
     /// The index of the action in the array determines which number should be used to invoke
-    /// that action.
-    ActionMappingUpdate([Action; 10]),
+    /// that action. Not every action has to be present in the mapping.
+    ActionMappingUpdate(Vec<Action>),
+    /// Result of a [`ClientToServerMsg::PerformAction`] message.
+    ActionResult {
+        action: Action,
+        outcome: ActionOutcome,
+    },
     /// This message is returned by the server when an error occurs.
     Error(String),
 }
+
+/// Errors after which the server disconnects the client, as reported by the chat servers.
+const FATAL_ERRORS: [&str; 5] = [
+    "Username already taken",
+    "Unexpected message received",
+    "Server is full",
+    "Timed out waiting for Join",
+    "Timeouted",
+];
+
+/// Error received in [`ServerToClientMsg::Error`], split by whether the client can continue.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ServerError {
+    /// The server disconnects the client after this error.
+    Fatal(String),
+    /// The client can continue after this error, e.g. "User <to> does not exist".
+    Recoverable(String),
+}
+
+impl ServerError {
+    /// Classifies the error `message` sent by the server.
+    /// Unknown errors are considered to be recoverable, if the server disconnects the client
+    /// anyway, the client notices it on the next read.
+    pub fn classify(message: String) -> Self {
+        if FATAL_ERRORS.contains(&message.as_str()) {
+            ServerError::Fatal(message)
+        } else {
+            ServerError::Recoverable(message)
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Eq, PartialEq)]
+pub enum ActionOutcome {
+    /// The action was performed.
+    Performed,
+    /// The action cannot be performed again yet.
+    OnCooldown,
+    /// The action was refused by the server for the given reason.
+    Rejected(String),
+}